@@ -0,0 +1,97 @@
+use std::{
+    hint::black_box,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Instant,
+};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use ringhash::Consistent;
+
+fn bench_add(c: &mut Criterion) {
+    let members = (0..5000).map(|i| format!("cache{i}")).collect::<Vec<_>>();
+    c.bench_function("add 5000 members", |b| {
+        b.iter(|| {
+            let ring = Consistent::new();
+            for member in members.iter() {
+                ring.add(member.clone());
+            }
+            ring
+        })
+    });
+}
+
+fn bench_get_n(c: &mut Criterion) {
+    let ring = Consistent::new();
+    ring.add_many((0..500).map(|i| format!("cache{i}")));
+    c.bench_function("get_n 500 of 500 members", |b| {
+        b.iter(|| ring.get_n("user_mcnulty", 500).unwrap())
+    });
+}
+
+fn bench_get(c: &mut Criterion) {
+    let members = (0..100).map(|i| format!("cache-server-{i}.example.com"));
+    let ring = Consistent::new();
+    ring.add_many(members.clone());
+    let arc_ring = Consistent::<Arc<str>>::default();
+    arc_ring.add_many(members.map(Arc::<str>::from));
+    c.bench_function("get 100 members", |b| {
+        b.iter(|| ring.get("user_mcnulty").unwrap())
+    });
+    c.bench_function("get 100 Arc<str> members", |b| {
+        b.iter(|| arc_ring.get("user_mcnulty").unwrap())
+    });
+    c.bench_function("get_with 100 members", |b| {
+        b.iter(|| ring.get_with("user_mcnulty", |v| v.len()).unwrap())
+    });
+}
+
+fn bench_get_concurrent(c: &mut Criterion) {
+    const READERS: u64 = 4;
+    let ring = Consistent::new();
+    ring.add_many((0..100).map(|i| format!("cache-server-{i}.example.com")));
+    let keys = (0..64).map(|i| format!("user{i}")).collect::<Vec<_>>();
+    // Readers share the ring with a writer churning a member, as a routing
+    // table being updated under load would.
+    c.bench_function("get 100 members, 4 readers and a writer", |b| {
+        b.iter_custom(|iters| {
+            let stop = AtomicBool::new(false);
+            thread::scope(|s| {
+                s.spawn(|| {
+                    while !stop.load(Ordering::Relaxed) {
+                        ring.add("cache-server-churn.example.com");
+                        ring.remove("cache-server-churn.example.com");
+                    }
+                });
+                let start = Instant::now();
+                let readers = (0..READERS)
+                    .map(|_| {
+                        s.spawn(|| {
+                            for i in 0..iters / READERS {
+                                black_box(ring.get(&keys[i as usize % keys.len()]).unwrap());
+                            }
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                for reader in readers {
+                    reader.join().unwrap();
+                }
+                let elapsed = start.elapsed();
+                stop.store(true, Ordering::Relaxed);
+                elapsed
+            })
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_add,
+    bench_get_n,
+    bench_get,
+    bench_get_concurrent
+);
+criterion_main!(benches);
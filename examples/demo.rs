@@ -5,7 +5,7 @@ fn main() {
     c.add("cacheA");
     c.add("cacheB");
     c.add("cacheC");
-    let users = vec![
+    let users = [
         "user_mcnulty",
         "user_bunk",
         "user_omar",
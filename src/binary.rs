@@ -0,0 +1,388 @@
+use std::mem::size_of;
+
+use faststr::FastStr;
+
+use crate::{
+    CollisionPolicy, Consistent, Error, Point, ReplicaKeyFormat, RingHasher, MAX_REPLICAS,
+};
+
+const MAGIC: &[u8] = b"RH";
+
+/// The current layout version. Version 1 lacks the placement flags byte,
+/// versions before 3 the minimum replica spacing, versions before 4 the
+/// member tags, versions before 5 the key namespace and versions before 6
+/// the member weights.
+const VERSION: u8 = 6;
+
+/// Placement flag bit set for rings using balanced placement.
+const BALANCED: u8 = 1;
+
+/// Placement flag bit set for rings with case-insensitive lookups.
+const CASE_INSENSITIVE: u8 = 2;
+
+impl<H: RingHasher> Consistent<FastStr, H> {
+    /// Encodes the ring, virtual nodes included, as a compact binary blob that
+    /// [`from_bytes`](Self::from_bytes) loads back without rehashing.
+    ///
+    /// The layout is versioned and little-endian: the configuration, then the
+    /// members with their replica counts, then the sorted virtual nodes, each
+    /// referring to its member by index, then the tagged members' tags, then
+    /// the weights of the members whose weight is not their replica count
+    /// over the ring's.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let sorted_hashes = self.sorted_hashes.lock();
+        let mut members = self
+            .members
+            .iter()
+            .map(|m| {
+                let replicas = self.replicas.get(m.key()).map_or(0, |r| *r);
+                (m.key().to_owned(), replicas)
+            })
+            .collect::<Vec<_>>();
+        members.sort();
+        let number_of_replicas = self.number_of_replicas();
+
+        let mut buf = Vec::with_capacity(
+            32 + members.len() * 16 + sorted_hashes.len() * (size_of::<H::Point>() + 4),
+        );
+        buf.extend_from_slice(MAGIC);
+        buf.push(VERSION);
+        buf.push(size_of::<H::Point>() as u8);
+        buf.extend_from_slice(&(number_of_replicas as u64).to_le_bytes());
+        buf.push(match self.replica_key_format {
+            ReplicaKeyFormat::IndexPrefix => 0,
+            ReplicaKeyFormat::MemberPrefix => 1,
+            ReplicaKeyFormat::Ketama => 2,
+        });
+        buf.push(match self.collision_policy {
+            CollisionPolicy::Probe => 0,
+            CollisionPolicy::Overwrite => 1,
+            CollisionPolicy::KeepLowest => 2,
+        });
+        let mut flags = 0;
+        if self.balanced_placement {
+            flags |= BALANCED;
+        }
+        if self.case_insensitive {
+            flags |= CASE_INSENSITIVE;
+        }
+        buf.push(flags);
+        self.min_replica_spacing.write_le(&mut buf);
+        let key_namespace = self.key_namespace.as_deref().unwrap_or_default();
+        buf.extend_from_slice(&(key_namespace.len() as u32).to_le_bytes());
+        buf.extend_from_slice(key_namespace.as_bytes());
+        buf.extend_from_slice(&self.hash_seed.to_le_bytes());
+        buf.extend_from_slice(&(members.len() as u32).to_le_bytes());
+        for (key, replicas) in &members {
+            buf.extend_from_slice(&(key.len() as u32).to_le_bytes());
+            buf.extend_from_slice(key.as_bytes());
+            buf.extend_from_slice(&(*replicas as u64).to_le_bytes());
+        }
+        buf.extend_from_slice(&(sorted_hashes.len() as u32).to_le_bytes());
+        for (hash, member) in sorted_hashes.iter() {
+            let i = members.partition_point(|(key, _)| key < member);
+            hash.write_le(&mut buf);
+            buf.extend_from_slice(&(i as u32).to_le_bytes());
+        }
+        let tagged = members
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (key, _))| Some((i, self.tag(key)?)))
+            .collect::<Vec<_>>();
+        buf.extend_from_slice(&(tagged.len() as u32).to_le_bytes());
+        for (i, tag) in tagged {
+            buf.extend_from_slice(&(i as u32).to_le_bytes());
+            buf.extend_from_slice(&(tag.len() as u32).to_le_bytes());
+            buf.extend_from_slice(tag.as_bytes());
+        }
+        let weighted = members
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (key, replicas))| {
+                let weight = *self.weights.get(key)?;
+                (weight != *replicas as f64 / number_of_replicas as f64).then_some((i, weight))
+            })
+            .collect::<Vec<_>>();
+        buf.extend_from_slice(&(weighted.len() as u32).to_le_bytes());
+        for (i, weight) in weighted {
+            buf.extend_from_slice(&(i as u32).to_le_bytes());
+            buf.extend_from_slice(&weight.to_bits().to_le_bytes());
+        }
+        buf
+    }
+
+    /// Loads a ring encoded by [`to_bytes`](Self::to_bytes), taking the virtual
+    /// nodes as they are rather than rehashing the members. Errors with
+    /// [`Error::Deserialize`] if `bytes` is malformed.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error>
+    where
+        H: Default,
+    {
+        let mut r = Reader(bytes);
+        if r.take(MAGIC.len())? != MAGIC {
+            return Err(Error::Deserialize("unknown format"));
+        }
+        let version = r.u8()?;
+        if !(1..=VERSION).contains(&version) {
+            return Err(Error::Deserialize("unsupported version"));
+        }
+        if r.u8()? as usize != size_of::<H::Point>() {
+            return Err(Error::Deserialize("ring width mismatch"));
+        }
+        let number_of_replicas = r.replicas()?;
+        let replica_key_format = match r.u8()? {
+            0 => ReplicaKeyFormat::IndexPrefix,
+            1 => ReplicaKeyFormat::MemberPrefix,
+            2 => ReplicaKeyFormat::Ketama,
+            _ => return Err(Error::Deserialize("unknown replica key format")),
+        };
+        let collision_policy = match r.u8()? {
+            0 => CollisionPolicy::Probe,
+            1 => CollisionPolicy::Overwrite,
+            2 => CollisionPolicy::KeepLowest,
+            _ => return Err(Error::Deserialize("unknown collision policy")),
+        };
+        let flags = if version >= 2 { r.u8()? } else { 0 };
+        if flags & !(BALANCED | CASE_INSENSITIVE) != 0 {
+            return Err(Error::Deserialize("unknown placement flags"));
+        }
+        let min_replica_spacing = if version >= 3 {
+            r.point()?
+        } else {
+            H::Point::ZERO
+        };
+        let key_namespace = if version >= 5 { r.str()? } else { "" };
+        let c = Self::default()
+            .with_number_of_replicas(number_of_replicas)
+            .with_replica_key_format(replica_key_format)
+            .with_collision_policy(collision_policy)
+            .with_balanced_placement(flags & BALANCED != 0)
+            .with_case_insensitive(flags & CASE_INSENSITIVE != 0)
+            .with_min_replica_spacing(min_replica_spacing)
+            .with_key_namespace(FastStr::new(key_namespace))
+            .with_hash_seed(r.u64()?);
+
+        let mut keys = Vec::new();
+        for _ in 0..r.u32()? {
+            let key = FastStr::new(r.str()?);
+            if c.members.insert(key.clone(), key.clone()).is_some() {
+                return Err(Error::Deserialize("duplicate member"));
+            }
+            let replicas = r.replicas()?;
+            c.replicas.insert(key.clone(), replicas);
+            c.weights
+                .insert(key.clone(), replicas as f64 / c.number_of_replicas() as f64);
+            keys.push(key);
+        }
+        c.sorted_hashes.set_members(keys.len());
+
+        let mut sorted_hashes = c.sorted_hashes.write();
+        for _ in 0..r.u32()? {
+            let hash = r.point::<H::Point>()?;
+            let key = keys
+                .get(r.u32()? as usize)
+                .ok_or(Error::Deserialize("unknown member index"))?;
+            if sorted_hashes.last().is_some_and(|(prev, _)| *prev >= hash) {
+                return Err(Error::Deserialize("virtual nodes out of order"));
+            }
+            c.circle.insert(hash, key.clone());
+            sorted_hashes.push((hash, key.clone()));
+        }
+        if version >= 4 {
+            for _ in 0..r.u32()? {
+                let key = keys
+                    .get(r.u32()? as usize)
+                    .ok_or(Error::Deserialize("unknown member index"))?;
+                let tag = r.str()?;
+                c.tags.insert(key.clone(), FastStr::new(tag));
+            }
+        }
+        if version >= 6 {
+            for _ in 0..r.u32()? {
+                let key = keys
+                    .get(r.u32()? as usize)
+                    .ok_or(Error::Deserialize("unknown member index"))?;
+                let weight = f64::from_bits(r.u64()?);
+                if !(weight.is_finite() && weight > 0.0) {
+                    return Err(Error::Deserialize("invalid member weight"));
+                }
+                c.weights.insert(key.clone(), weight);
+            }
+        }
+        if !r.0.is_empty() {
+            return Err(Error::Deserialize("trailing bytes"));
+        }
+        drop(sorted_hashes);
+        Ok(c)
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        if self.0.len() < n {
+            return Err(Error::Deserialize("unexpected end of input"));
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn replicas(&mut self) -> Result<usize, Error> {
+        let replicas = self.u64()?;
+        if replicas > MAX_REPLICAS as u64 {
+            return Err(Error::Deserialize("replica count out of range"));
+        }
+        Ok(replicas as usize)
+    }
+
+    fn str(&mut self) -> Result<&'a str, Error> {
+        let len = self.u32()? as usize;
+        std::str::from_utf8(self.take(len)?).map_err(|_| Error::Deserialize("invalid utf-8"))
+    }
+
+    fn point<P: Point>(&mut self) -> Result<P, Error> {
+        P::read_le(self.take(size_of::<P>())?).ok_or(Error::Deserialize("unexpected end of input"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CollisionPolicy, Consistent, Consistent64, Error, ReplicaKeyFormat};
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let c = Consistent::new()
+            .with_number_of_replicas(10)
+            .with_replica_key_format(ReplicaKeyFormat::Ketama)
+            .with_collision_policy(CollisionPolicy::Overwrite)
+            .with_hash_seed(7);
+        c.add("abcdefg");
+        c.add("opqrstu");
+        c.add_weighted("hijklmn", 3);
+        let bytes = c.to_bytes();
+        let d: Consistent = Consistent::from_bytes(&bytes).unwrap();
+        assert_eq!(d.number_of_replicas(), 10);
+        assert_eq!(d.replica_key_format, ReplicaKeyFormat::Ketama);
+        assert_eq!(d.collision_policy, CollisionPolicy::Overwrite);
+        assert_eq!(d.hash_seed, 7);
+        assert_eq!(d.len(), 3);
+        assert_eq!(*d.sorted_hashes.read(), *c.sorted_hashes.read());
+        for key in ["user_mcnulty", "user_bunk", "user_omar"] {
+            assert_eq!(d.get(key).unwrap(), c.get(key).unwrap());
+        }
+        assert_eq!(d.to_bytes(), bytes);
+
+        d.remove("hijklmn");
+        assert_eq!(d.circle.len(), 20);
+
+        c.add_tagged("uvwxyz", "rackB");
+        let d: Consistent = Consistent::from_bytes(&c.to_bytes()).unwrap();
+        assert_eq!(d.tag("uvwxyz").unwrap(), "rackB");
+        assert_eq!(d, c);
+
+        let empty = Consistent::new();
+        let d: Consistent = Consistent::from_bytes(&empty.to_bytes()).unwrap();
+        assert!(d.is_empty());
+
+        let c: Consistent = Consistent::from_replica_counts([("abcdefg", 7)]);
+        c.set_number_of_replicas(1);
+        let d: Consistent = Consistent::from_bytes(&c.to_bytes()).unwrap();
+        d.set_number_of_replicas(20);
+        assert_eq!(*d.replicas.get("abcdefg").unwrap(), 7);
+    }
+
+    #[test]
+    fn test_bytes_old_versions() {
+        let c = Consistent::new().with_hash_seed(7);
+        c.add("abcdefg");
+        c.add("opqrstu");
+        let mut bytes = c.to_bytes();
+        // Version 5 had no member weights after the member tags.
+        bytes[2] = 5;
+        bytes.truncate(bytes.len() - 4);
+        let d: Consistent = Consistent::from_bytes(&bytes).unwrap();
+        assert_eq!(d, c);
+        // Version 4 had no key namespace after the minimum replica spacing.
+        bytes[2] = 4;
+        bytes.drain(19..23);
+        let d: Consistent = Consistent::from_bytes(&bytes).unwrap();
+        assert_eq!(d, c);
+        // Version 3 had no member tags after the virtual nodes.
+        bytes[2] = 3;
+        bytes.truncate(bytes.len() - 4);
+        let d: Consistent = Consistent::from_bytes(&bytes).unwrap();
+        assert_eq!(d, c);
+        // Version 2 had no minimum replica spacing after the placement flags.
+        bytes[2] = 2;
+        bytes.drain(15..19);
+        let d: Consistent = Consistent::from_bytes(&bytes).unwrap();
+        assert_eq!(d, c);
+        // Version 1 had no placement flags byte after the collision policy.
+        bytes[2] = 1;
+        bytes.remove(14);
+        let d: Consistent = Consistent::from_bytes(&bytes).unwrap();
+        assert_eq!(d, c);
+
+        let balanced = Consistent::new()
+            .with_balanced_placement(true)
+            .with_min_replica_spacing(1 << 20);
+        balanced.add("abcdefg");
+        let d: Consistent = Consistent::from_bytes(&balanced.to_bytes()).unwrap();
+        assert!(d.balanced_placement);
+        assert_eq!(d.min_replica_spacing, 1 << 20);
+
+        let folding = Consistent::new()
+            .with_case_insensitive(true)
+            .with_key_namespace("tenantA:");
+        let d: Consistent = Consistent::from_bytes(&folding.to_bytes()).unwrap();
+        assert!(d.case_insensitive && !d.balanced_placement);
+        assert_eq!(d, folding);
+    }
+
+    #[test]
+    fn test_bytes_malformed() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        let bytes = c.to_bytes();
+        for input in [
+            &bytes[..bytes.len() - 1],
+            &bytes[1..],
+            &[bytes.clone(), vec![0]].concat(),
+        ] {
+            assert!(matches!(
+                <Consistent>::from_bytes(input),
+                Err(Error::Deserialize(_))
+            ));
+        }
+        assert!(matches!(
+            Consistent64::from_bytes(&bytes),
+            Err(Error::Deserialize("ring width mismatch"))
+        ));
+
+        // A corrupted replica count, here the member's, is rejected before
+        // anything is placed for it.
+        let at = bytes.windows(7).position(|w| w == b"abcdefg").unwrap() + 7;
+        for (at, replicas) in [(at, u64::MAX), (at, 1 << 32), (4, u64::MAX)] {
+            let mut input = bytes.clone();
+            input[at..at + 8].copy_from_slice(&replicas.to_le_bytes());
+            assert!(matches!(
+                <Consistent>::from_bytes(&input),
+                Err(Error::Deserialize("replica count out of range"))
+            ));
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use std::{
+    collections::HashMap,
+    hash::BuildHasher,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use faststr::FastStr;
+
+use crate::{Consistent, Error, FxDashMap, Node, RingHasher};
+
+/// Tracks the in-flight load of each member, keyed by [`Node::node_key`], for
+/// [`Consistent::get_bounded`].
+#[derive(Debug, Default)]
+pub struct LoadTracker {
+    loads: FxDashMap<FastStr, usize>,
+    total: AtomicUsize,
+}
+
+impl LoadTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn inc(&self, member: impl AsRef<str>) {
+        match self.loads.get_mut(member.as_ref()) {
+            Some(mut load) => *load += 1,
+            None => *self.loads.entry(FastStr::new(member)).or_default() += 1,
+        }
+        self.total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn dec(&self, member: impl AsRef<str>) {
+        if let Some(mut load) = self.loads.get_mut(member.as_ref()) {
+            if *load > 0 {
+                *load -= 1;
+                self.total.fetch_sub(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn load(&self, member: impl AsRef<str>) -> usize {
+        self.loads.get(member.as_ref()).map_or(0, |load| *load)
+    }
+
+    pub fn total(&self) -> usize {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    /// Returns the average load over `members` members.
+    pub fn average(&self, members: usize) -> f64 {
+        if members == 0 {
+            return 0.0;
+        }
+        self.total() as f64 / members as f64
+    }
+}
+
+impl<T: Node, H: RingHasher> Consistent<T, H> {
+    /// Looks up `name` with consistent hashing with bounded loads: walking
+    /// clockwise, members whose load has reached
+    /// `ceil((total_load + 1) / members * (1 + epsilon))` are skipped, so no
+    /// member takes much more than its fair share. `total_load` is
+    /// [`load.total()`](LoadTracker::total), the `+ 1` being the key looked
+    /// up, and `members` is the number of enabled members.
+    ///
+    /// `epsilon` should be non-negative; the smaller it is, the tighter the
+    /// bound and the more keys move away from their natural owner. Disabled
+    /// members are skipped and left out of the average, as with
+    /// [`get`](Self::get). If every enabled member is at the bound, the
+    /// natural owner is returned. The observer is told about the member
+    /// picked.
+    pub fn get_bounded(
+        &self,
+        name: impl AsRef<str>,
+        load: &LoadTracker,
+        epsilon: f64,
+    ) -> Result<T, Error> {
+        let name = name.as_ref().as_bytes();
+        let key = self.lookup_key(name);
+        let sorted_hashes = self.sorted_hashes.read();
+        let members = sorted_hashes
+            .members()
+            .saturating_sub(self.disabled.len())
+            .max(1);
+        let capacity =
+            ((load.total() + 1) as f64 / members as f64 * (1.0 + epsilon)).ceil() as usize;
+        match self.find_map_in(
+            &sorted_hashes,
+            key,
+            Some(name),
+            |v| load.load(v.node_key()) < capacity,
+            T::clone,
+        ) {
+            Err(Error::EmptyCircle) => {
+                self.find_map_in(&sorted_hashes, key, Some(name), |_| true, T::clone)
+            }
+            res => res,
+        }
+    }
+
+    /// Looks up `name` against hard per-member capacities: walking clockwise,
+    /// members whose `current` load has reached their entry in `capacities`
+    /// are skipped. Members missing from `capacities` are unbounded, and
+    /// those missing from `current` carry no load. Errors with
+    /// [`Error::AtCapacity`] if every enabled member is full.
+    pub fn get_capacity_aware<S: BuildHasher>(
+        &self,
+        name: impl AsRef<str>,
+        capacities: &HashMap<FastStr, usize, S>,
+        current: &HashMap<FastStr, usize, S>,
+    ) -> Result<T, Error> {
+        self.find(name.as_ref().as_bytes(), |v| {
+            let key = v.node_key();
+            capacities
+                .get(&key)
+                .is_none_or(|capacity| current.get(&key).map_or(0, |load| *load) < *capacity)
+        })
+        .map_err(|e| match e {
+            Error::EmptyCircle if self.len() > self.disabled.len() => Error::AtCapacity,
+            e => e,
+        })
+    }
+
+    /// Like [`get`](Self::get), but also counts the hit against the picked
+    /// member if the ring was built
+    /// [`with_hit_counting`](Self::with_hit_counting), and towards its hit
+    /// rate if it was built with a rate window.
+    pub fn get_counting(&self, name: impl AsRef<str>) -> Result<T, Error> {
+        let member = self.get(name)?;
+        #[cfg(feature = "hit-rates")]
+        if let Some(rates) = &self.rates {
+            rates.record(member.node_key());
+        }
+        if let Some(hits) = &self.hits {
+            let key = member.node_key();
+            match hits.get(&key) {
+                Some(count) => {
+                    count.fetch_add(1, Ordering::Relaxed);
+                }
+                None => {
+                    hits.entry(key).or_default().fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+        Ok(member)
+    }
+
+    /// Returns the hits counted by [`get_counting`](Self::get_counting) per
+    /// member key since the last [`reset_hits`](Self::reset_hits). Counts
+    /// outlive the removal of their member.
+    pub fn hits(&self) -> HashMap<FastStr, u64> {
+        self.hits.as_ref().map_or_else(HashMap::new, |hits| {
+            hits.iter()
+                .map(|h| (h.key().to_owned(), h.load(Ordering::Relaxed)))
+                .collect()
+        })
+    }
+
+    pub fn reset_hits(&self) {
+        if let Some(hits) = &self.hits {
+            hits.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use faststr::FastStr;
+
+    use crate::{Consistent, Error, LoadTracker};
+
+    #[test]
+    fn test_get_bounded() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        c.add("opqrstu");
+        c.add("hijklmn");
+        let load = LoadTracker::new();
+        let owner = c.get("asdfsadfsadf").unwrap();
+        assert_eq!(c.get_bounded("asdfsadfsadf", &load, 0.25).unwrap(), owner);
+        for _ in 0..10 {
+            load.inc(&owner);
+        }
+        assert_eq!(load.total(), 10);
+        let other = c.get_bounded("asdfsadfsadf", &load, 0.25).unwrap();
+        assert_ne!(other, owner);
+        for _ in 0..10 {
+            load.dec(&owner);
+        }
+        assert_eq!(load.load(&owner), 0);
+        assert_eq!(c.get_bounded("asdfsadfsadf", &load, 0.25).unwrap(), owner);
+
+        c.disable(&owner);
+        for i in 0..1000 {
+            let key = format!("user{i}");
+            let member = c.get_bounded(&key, &load, 0.25).unwrap();
+            assert_ne!(member, owner);
+            assert_eq!(member, c.get(&key).unwrap());
+        }
+        c.disable(&other);
+        for _ in 0..10 {
+            load.inc(c.get("asdfsadfsadf").unwrap());
+        }
+        assert_eq!(
+            c.get_bounded("asdfsadfsadf", &load, 0.25).unwrap(),
+            c.get("asdfsadfsadf").unwrap()
+        );
+        c.disable(c.get("asdfsadfsadf").unwrap());
+        assert!(matches!(
+            c.get_bounded("asdfsadfsadf", &load, 0.25),
+            Err(Error::EmptyCircle)
+        ));
+    }
+
+    #[test]
+    fn test_get_counting() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        c.get_counting("user_mcnulty").unwrap();
+        assert!(c.hits().is_empty());
+
+        let c = Consistent::new().with_hit_counting();
+        c.add("abcdefg");
+        c.add("opqrstu");
+        for i in 0..100 {
+            c.get_counting(format!("user{i}")).unwrap();
+        }
+        let hits = c.hits();
+        assert_eq!(hits.values().sum::<u64>(), 100);
+        assert_eq!(c.clone().hits(), hits);
+        c.reset_hits();
+        assert!(c.hits().is_empty());
+    }
+
+    #[test]
+    fn test_get_capacity_aware() {
+        let c = Consistent::new();
+        for elt in ["abcdefg", "hijklmn", "opqrstu"] {
+            c.add(elt);
+        }
+        let key = "user_mcnulty";
+        let owners = c.get_n(key, 3).unwrap();
+        let mut capacities = HashMap::<FastStr, usize>::new();
+        let mut current = HashMap::new();
+        assert_eq!(
+            c.get_capacity_aware(key, &capacities, &current).unwrap(),
+            owners[0]
+        );
+
+        capacities.insert(owners[0].clone(), 2);
+        capacities.insert(owners[1].clone(), 1);
+        current.insert(owners[0].clone(), 1);
+        assert_eq!(
+            c.get_capacity_aware(key, &capacities, &current).unwrap(),
+            owners[0]
+        );
+        current.insert(owners[0].clone(), 2);
+        assert_eq!(
+            c.get_capacity_aware(key, &capacities, &current).unwrap(),
+            owners[1]
+        );
+        current.insert(owners[1].clone(), 1);
+        assert_eq!(
+            c.get_capacity_aware(key, &capacities, &current).unwrap(),
+            owners[2]
+        );
+        capacities.insert(owners[2].clone(), 0);
+        assert!(matches!(
+            c.get_capacity_aware(key, &capacities, &current),
+            Err(Error::AtCapacity)
+        ));
+        c.clear();
+        assert!(matches!(
+            c.get_capacity_aware(key, &capacities, &current),
+            Err(Error::EmptyCircle)
+        ));
+    }
+}
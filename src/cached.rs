@@ -0,0 +1,190 @@
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use faststr::FastStr;
+
+use crate::{Consistent, Error, FxDashMap, FxHasher32, Node, RingHasher};
+
+/// A [`Consistent`] that memoizes [`get_n`](Consistent::get_n) per key and
+/// count, for workloads that keep asking for the same replica sets while the
+/// ring is stable.
+///
+/// Changing the ring through the wrapper clears the cache. The cache is not
+/// bounded, so it grows with the distinct keys looked up between changes.
+pub struct CachedConsistent<T = FastStr, H: RingHasher = FxHasher32> {
+    ring: Consistent<T, H>,
+    cache: ReplicaCache<T>,
+}
+
+/// Memoized `get_n` results, each tagged with the generation of the ring it
+/// was computed against, so that a result computed while the ring was
+/// changing is never served afterwards.
+struct ReplicaCache<T> {
+    entries: FxDashMap<(FastStr, usize), (u64, Vec<T>)>,
+    generation: AtomicU64,
+}
+
+impl<T: Clone> ReplicaCache<T> {
+    fn get(&self, key: &(FastStr, usize), generation: u64) -> Option<Vec<T>> {
+        let entry = self.entries.get(key)?;
+        (entry.0 == generation).then(|| entry.1.clone())
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    fn invalidate(&self) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        self.entries.clear();
+    }
+}
+
+impl<T: Node, H: RingHasher> CachedConsistent<T, H> {
+    pub fn new(ring: Consistent<T, H>) -> Self {
+        Self {
+            ring,
+            cache: ReplicaCache {
+                entries: FxDashMap::default(),
+                generation: AtomicU64::new(0),
+            },
+        }
+    }
+
+    /// Returns the wrapped ring, for the lookups the wrapper does not cache.
+    /// Changes made through it bypass the wrapper, so follow them with
+    /// [`invalidate`](Self::invalidate).
+    pub fn ring(&self) -> &Consistent<T, H> {
+        &self.ring
+    }
+
+    pub fn into_inner(self) -> Consistent<T, H> {
+        self.ring
+    }
+
+    /// Like [`Consistent::get_n`], but answers from the cache if the same key
+    /// and count were looked up since the ring last changed. Errors are not
+    /// cached.
+    pub fn get_n(&self, name: impl AsRef<str>, n: usize) -> Result<Vec<T>, Error> {
+        let key = (FastStr::new(name.as_ref()), n);
+        // Read before the lookup, so a change racing with it leaves the
+        // result tagged as stale.
+        let generation = self.cache.generation();
+        if let Some(replicas) = self.cache.get(&key, generation) {
+            return Ok(replicas);
+        }
+        let replicas = self.ring.get_n(&key.0, n)?;
+        self.cache
+            .entries
+            .insert(key, (generation, replicas.clone()));
+        Ok(replicas)
+    }
+
+    /// Returns the number of cached replica sets.
+    pub fn cached(&self) -> usize {
+        self.cache.entries.len()
+    }
+
+    /// Clears the cache, after changing the ring through
+    /// [`ring`](Self::ring).
+    pub fn invalidate(&self) {
+        self.cache.invalidate();
+    }
+
+    pub fn add(&self, elt: impl Into<T>) {
+        self.ring.add(elt);
+        self.invalidate();
+    }
+
+    pub fn add_weighted(&self, elt: impl Into<T>, weight: usize) {
+        self.ring.add_weighted(elt, weight);
+        self.invalidate();
+    }
+
+    pub fn add_many(&self, elts: impl IntoIterator<Item = impl Into<T>>) {
+        self.ring.add_many(elts);
+        self.invalidate();
+    }
+
+    pub fn remove(&self, elt: impl AsRef<str>) {
+        self.ring.remove(elt);
+        self.invalidate();
+    }
+
+    pub fn remove_many(&self, elts: impl IntoIterator<Item = impl AsRef<str>>) {
+        self.ring.remove_many(elts);
+        self.invalidate();
+    }
+
+    pub fn set(&self, elts: Vec<impl Into<T>>) {
+        self.ring.set(elts);
+        self.invalidate();
+    }
+
+    pub fn clear(&self) {
+        self.ring.clear();
+        self.invalidate();
+    }
+
+    pub fn disable(&self, elt: impl AsRef<str>) {
+        self.ring.disable(elt);
+        self.invalidate();
+    }
+
+    pub fn enable(&self, elt: impl AsRef<str>) {
+        self.ring.enable(elt);
+        self.invalidate();
+    }
+}
+
+impl<T: Node, H: RingHasher> From<Consistent<T, H>> for CachedConsistent<T, H> {
+    fn from(ring: Consistent<T, H>) -> Self {
+        Self::new(ring)
+    }
+}
+
+impl<T, H: RingHasher> fmt::Debug for CachedConsistent<T, H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CachedConsistent")
+            .field("ring", &self.ring)
+            .field("cached", &self.cache.entries.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CachedConsistent, Consistent};
+
+    #[test]
+    fn test_cached_get_n() {
+        let c = CachedConsistent::new(Consistent::new());
+        c.add_many(["abcdefg", "hijklmn", "opqrstu"]);
+        let replicas = c.get_n("user_mcnulty", 2).unwrap();
+        assert_eq!(replicas, c.ring().get_n("user_mcnulty", 2).unwrap());
+        assert_eq!(c.get_n("user_mcnulty", 2).unwrap(), replicas);
+        assert_eq!(c.get_n("user_mcnulty", 3).unwrap().len(), 3);
+        assert_eq!(c.cached(), 2);
+
+        c.disable(&replicas[0]);
+        assert_eq!(c.cached(), 0);
+        assert!(!c.get_n("user_mcnulty", 2).unwrap().contains(&replicas[0]));
+        c.enable(&replicas[0]);
+        c.remove(&replicas[1]);
+        assert!(!c.get_n("user_mcnulty", 2).unwrap().contains(&replicas[1]));
+        c.add(replicas[1].clone());
+        assert_eq!(c.get_n("user_mcnulty", 2).unwrap(), replicas);
+
+        c.ring().remove(&replicas[0]);
+        c.invalidate();
+        assert_eq!(
+            c.get_n("user_mcnulty", 2).unwrap(),
+            c.ring().get_n("user_mcnulty", 2).unwrap()
+        );
+        c.clear();
+        assert!(c.get_n("user_mcnulty", 2).is_err());
+        assert_eq!(c.cached(), 0);
+    }
+}
@@ -0,0 +1,225 @@
+use alloc::vec::Vec;
+use core::{fmt::Debug, hash::Hash};
+
+/// A position on the ring. Implemented for `u32` and `u64`.
+pub trait Point: Copy + Ord + Hash + Debug + Send + Sync + 'static {
+    const ZERO: Self;
+    const MAX: Self;
+
+    fn wrapping_sub(self, rhs: Self) -> Self;
+
+    fn saturating_add(self, rhs: Self) -> Self;
+
+    /// Returns the fraction of the whole ring that an arc of this length spans.
+    fn as_fraction(self) -> f64;
+
+    /// Appends the little-endian encoding of `self` to `buf`.
+    fn write_le(self, buf: &mut Vec<u8>);
+
+    /// Decodes a point from the first `size_of::<Self>()` bytes of `bytes`,
+    /// if there are enough.
+    fn read_le(bytes: &[u8]) -> Option<Self>;
+}
+
+macro_rules! impl_point {
+    ($($t:ty),*) => {
+        $(
+            impl Point for $t {
+                const ZERO: Self = 0;
+                const MAX: Self = <$t>::MAX;
+
+                fn wrapping_sub(self, rhs: Self) -> Self {
+                    <$t>::wrapping_sub(self, rhs)
+                }
+
+                fn saturating_add(self, rhs: Self) -> Self {
+                    <$t>::saturating_add(self, rhs)
+                }
+
+                fn as_fraction(self) -> f64 {
+                    self as f64 / (<$t>::MAX as f64 + 1.0)
+                }
+
+                fn write_le(self, buf: &mut Vec<u8>) {
+                    buf.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn read_le(bytes: &[u8]) -> Option<Self> {
+                    let bytes = bytes.first_chunk()?;
+                    Some(<$t>::from_le_bytes(*bytes))
+                }
+            }
+        )*
+    };
+}
+
+impl_point!(u32, u64);
+
+/// Hashes keys onto the ring, choosing its width through [`RingHasher::Point`].
+///
+/// Both virtual-node keys and lookup keys go through the same hasher, so two
+/// rings built with equivalent hashers route every key identically. Every
+/// [`Hasher32`] is a `RingHasher` over `u32`.
+pub trait RingHasher {
+    type Point: Point;
+
+    fn hash(&self, key: &[u8]) -> Self::Point;
+}
+
+/// Hashes keys onto the `u32` ring.
+pub trait Hasher32 {
+    fn hash32(&self, key: &[u8]) -> u32;
+}
+
+impl<H: Hasher32> RingHasher for H {
+    type Point = u32;
+
+    fn hash(&self, key: &[u8]) -> u32 {
+        self.hash32(key)
+    }
+}
+
+/// The default hasher, computing the same hashes as `fxhash::hash32`.
+///
+/// Bytes are hashed the way `str` hashes itself, so a key hashes the same
+/// whether it is looked up as `&str` or as `&[u8]`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FxHasher32;
+
+impl Hasher32 for FxHasher32 {
+    fn hash32(&self, key: &[u8]) -> u32 {
+        let mut hash = 0;
+        let mut chunks = key.chunks_exact(4);
+        for chunk in &mut chunks {
+            fx_word32(&mut hash, u32::from_ne_bytes(chunk.try_into().unwrap()));
+        }
+        for byte in chunks.remainder() {
+            fx_word32(&mut hash, *byte as u32);
+        }
+        fx_word32(&mut hash, 0xff);
+        hash
+    }
+}
+
+/// A hasher for the `u64` ring, computing the same hashes as `fxhash::hash64`.
+///
+/// The wider key space makes virtual-node collisions negligible even for very
+/// large fleets.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FxHasher64;
+
+impl RingHasher for FxHasher64 {
+    type Point = u64;
+
+    fn hash(&self, key: &[u8]) -> u64 {
+        let mut hash = 0;
+        let mut chunks = key.chunks_exact(8);
+        for chunk in &mut chunks {
+            fx_word64(&mut hash, u64::from_ne_bytes(chunk.try_into().unwrap()));
+        }
+        let mut rest = chunks.remainder();
+        if let Some((word, tail)) = rest.split_first_chunk::<4>() {
+            fx_word64(&mut hash, u32::from_ne_bytes(*word) as u64);
+            rest = tail;
+        }
+        for byte in rest {
+            fx_word64(&mut hash, *byte as u64);
+        }
+        fx_word64(&mut hash, 0xff);
+        hash
+    }
+}
+
+/// A 32-bit FNV-1a hasher whose output is part of this crate's stability
+/// guarantee: it is implemented here and pinned by tests, so rings built with
+/// it route every key the same way across crate and dependency upgrades.
+///
+/// Prefer it when the key-to-member mapping is persisted or shared between
+/// deployments; the default [`FxHasher32`] is faster.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StableHasher32;
+
+const FNV_OFFSET_BASIS32: u32 = 0x811c9dc5;
+const FNV_PRIME32: u32 = 0x01000193;
+
+impl Hasher32 for StableHasher32 {
+    fn hash32(&self, key: &[u8]) -> u32 {
+        key.iter().fold(FNV_OFFSET_BASIS32, |hash, byte| {
+            (hash ^ *byte as u32).wrapping_mul(FNV_PRIME32)
+        })
+    }
+}
+
+/// A hasher backed by 32-bit xxHash (`XXH32`), for routing that agrees with
+/// other languages' xxHash bindings. Requires the `xxhash` feature.
+#[cfg(feature = "xxhash")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct XxHasher32 {
+    seed: u32,
+}
+
+#[cfg(feature = "xxhash")]
+impl XxHasher32 {
+    pub fn with_seed(seed: u32) -> Self {
+        Self { seed }
+    }
+}
+
+#[cfg(feature = "xxhash")]
+impl Hasher32 for XxHasher32 {
+    fn hash32(&self, key: &[u8]) -> u32 {
+        xxhash_rust::xxh32::xxh32(key, self.seed)
+    }
+}
+
+// A port of the `fxhash` word mixing, so the hashers also work without `std`.
+// Feeding the bytes followed by `0xff` matches how `str` hashes itself.
+const FX_SEED64: u64 = 0x517cc1b727220a95;
+const FX_SEED32: u32 = FX_SEED64 as u32;
+
+fn fx_word32(hash: &mut u32, word: u32) {
+    *hash = (hash.rotate_left(5) ^ word).wrapping_mul(FX_SEED32);
+}
+
+fn fx_word64(hash: &mut u64, word: u64) {
+    *hash = (hash.rotate_left(5) ^ word).wrapping_mul(FX_SEED64);
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::{Consistent, Hasher32, StableHasher32};
+
+    #[test]
+    fn test_stable_hasher32() {
+        // Reference FNV-1a outputs.
+        assert_eq!(StableHasher32.hash32(b""), 0x811c9dc5);
+        assert_eq!(StableHasher32.hash32(b"a"), 0xe40c292c);
+        assert_eq!(StableHasher32.hash32(b"foobar"), 0xbf9cf968);
+
+        let c = Consistent::with_hasher(StableHasher32);
+        c.add("cacheA");
+        c.add("cacheB");
+        c.add("cacheC");
+        let routes =
+            ["user_mcnulty", "user_bunk", "user_omar", "user_bunny"].map(|key| c.get(key).unwrap());
+        assert_eq!(routes, ["cacheA", "cacheC", "cacheB", "cacheA"]);
+    }
+
+    #[cfg(feature = "xxhash")]
+    #[test]
+    fn test_xxhasher32() {
+        use crate::XxHasher32;
+
+        // Reference XXH32 outputs.
+        assert_eq!(XxHasher32::default().hash32(b""), 0x02cc5d05);
+        assert_eq!(XxHasher32::default().hash32(b"abc"), 0x32d153ff);
+        assert_eq!(XxHasher32::with_seed(1).hash32(b""), 0x0b2cb792);
+
+        let c = Consistent::with_hasher(XxHasher32::default()).with_number_of_replicas(1);
+        c.add("cacheA");
+        assert_eq!(
+            *c.sorted_hashes.read(),
+            vec![(XxHasher32::default().hash32(b"0cacheA"), "cacheA".into())]
+        );
+    }
+}
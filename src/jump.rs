@@ -0,0 +1,47 @@
+/// Maps `key` to a bucket in `0..num_buckets` with Lamping and Veach's jump
+/// consistent hash.
+///
+/// Unlike [`Consistent`](crate::Consistent) this needs no memory at all, but
+/// buckets are plain indices and can only be added or removed at the end of
+/// the range: growing from `n` to `n + 1` buckets moves `1 / (n + 1)` of the
+/// keys, all onto the new bucket.
+///
+/// # Panics
+///
+/// Panics if `num_buckets` is zero.
+pub fn jump_hash(mut key: u64, num_buckets: u32) -> u32 {
+    assert!(num_buckets > 0, "jump_hash needs at least one bucket");
+    let mut b = -1i64;
+    let mut j = 0i64;
+    while j < num_buckets as i64 {
+        b = j;
+        key = key.wrapping_mul(2862933555777941757).wrapping_add(1);
+        j = ((b + 1) as f64 * ((1u64 << 31) as f64 / ((key >> 33) + 1) as f64)) as i64;
+    }
+    b as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::jump_hash;
+
+    #[test]
+    fn test_jump_hash() {
+        // Test vectors of the reference implementation.
+        for (key, buckets, bucket) in [
+            (1, 1, 0),
+            (42, 57, 43),
+            (0xdead10cc, 1, 0),
+            (0xdead10cc, 666, 361),
+            (256, 1024, 520),
+        ] {
+            assert_eq!(jump_hash(key, buckets), bucket);
+        }
+
+        for key in 0..1000 {
+            let before = jump_hash(key, 10);
+            let after = jump_hash(key, 11);
+            assert!(after == before || after == 10);
+        }
+    }
+}
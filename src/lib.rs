@@ -9,6 +9,17 @@
 //! ringhash = "0.1"
 //! ```
 //!
+//! ## Features
+//!
+//! - `std` (default): the thread-safe `Consistent`. Without it the crate is `no_std` and only needs `alloc`, providing the single-threaded `LocalConsistent`.
+//! - `arc-swap`: publishes the sorted virtual nodes through an `ArcSwap` instead of an `RwLock`, so lookups never block on writers; changes copy the layout instead of updating it in place.
+//! - `hit-rates`: provides `Consistent::with_rate_window`, which tracks each member's hits per second by `get_counting` over a sliding window, e.g. for autoscaling.
+//! - `rand`: provides `Consistent::sample`, which picks a random member with probability equal to its share of the keyspace.
+//! - `rayon`: provides `Consistent::par_add_many`, which hashes and sorts the virtual nodes of a bulk load in parallel.
+//! - `serde`: implements `Serialize`/`Deserialize` for `Consistent`, persisting the replica count and the members.
+//! - `validate`: provides `Consistent::validate`, which checks the ring's internal invariants, for integration tests and fuzzing.
+//! - `xxhash`: provides `XxHasher32`, a 32-bit xxHash hasher for routing compatible with other xxHash-based implementations.
+//!
 //! ## Example
 //! ```rust
 //! use ringhash::Consistent;
@@ -46,333 +57,4242 @@
 //! ```
 //!
 
-use std::sync::atomic::{AtomicUsize, Ordering};
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::{format, string::String};
+use core::str::Utf8Error;
+#[cfg(feature = "std")]
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    fmt,
+    hash::BuildHasher,
+    ops::Range,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
+#[cfg(feature = "std")]
 use dashmap::{DashMap, DashSet};
 use faststr::FastStr;
-use fxhash::FxBuildHasher;
-use parking_lot::RwLock;
+#[cfg(feature = "std")]
+use fxhash::{FxBuildHasher, FxHashSet};
+#[cfg(feature = "std")]
+use observer::Observer;
+#[cfg(feature = "hit-rates")]
+use rates::HitRates;
+#[cfg(feature = "std")]
+use sorted::SortedHashes;
 
+#[cfg(feature = "std")]
+mod binary;
+#[cfg(feature = "std")]
+mod bounded;
+#[cfg(feature = "std")]
+mod cached;
+mod hasher;
+mod jump;
+mod local;
+mod node;
+#[cfg(feature = "std")]
+mod observer;
+#[cfg(feature = "rayon")]
+mod parallel;
+#[cfg(feature = "hit-rates")]
+mod rates;
+#[cfg(feature = "std")]
+mod rendezvous;
+#[cfg(feature = "rand")]
+mod sample;
+#[cfg(feature = "serde")]
+mod serde;
+#[cfg(feature = "std")]
+mod snapshot;
+#[cfg(feature = "std")]
+mod sorted;
+#[cfg(feature = "std")]
+mod stats;
+
+#[cfg(feature = "std")]
+pub use bounded::LoadTracker;
+#[cfg(feature = "std")]
+pub use cached::CachedConsistent;
+#[cfg(feature = "xxhash")]
+pub use hasher::XxHasher32;
+pub use hasher::{FxHasher32, FxHasher64, Hasher32, Point, RingHasher, StableHasher32};
+pub use jump::jump_hash;
+pub use local::LocalConsistent;
+pub use node::Node;
+#[cfg(feature = "std")]
+pub use observer::RingObserver;
+#[cfg(feature = "std")]
+pub use rendezvous::Rendezvous;
+#[cfg(feature = "std")]
+pub use snapshot::RingSnapshot;
+#[cfg(feature = "std")]
+pub use sorted::MemberRef;
+
+#[cfg(feature = "std")]
 type FxDashMap<K, V> = DashMap<K, V, FxBuildHasher>;
+#[cfg(feature = "std")]
 type FxDashSet<K> = DashSet<K, FxBuildHasher>;
 
-#[derive(Debug)]
-pub struct Consistent {
-    circle: FxDashMap<u32, FastStr>,
-    members: FxDashSet<FastStr>,
-    sorted_hashes: RwLock<Vec<u32>>,
-    number_of_replicas: usize,
-    count: AtomicUsize,
+/// A consistent hash ring of `T` members.
+///
+/// Members are identified by their [`Node::node_key`], so lookups and removals
+/// take the key as a string whatever the member type is.
+///
+/// Requires the `std` feature; [`LocalConsistent`] is the single-threaded
+/// counterpart for `no_std`.
+#[cfg(feature = "std")]
+pub struct Consistent<T = FastStr, H: RingHasher = FxHasher32> {
+    circle: FxDashMap<H::Point, T>,
+    members: FxDashMap<FastStr, T>,
+    replicas: FxDashMap<FastStr, usize>,
+    /// Each member's replica count per unit of `number_of_replicas`, which
+    /// its count is recomputed from when the latter changes.
+    weights: FxDashMap<FastStr, f64>,
+    disabled: FxDashSet<FastStr>,
+    tags: FxDashMap<FastStr, FastStr>,
+    sorted_hashes: SortedHashes<(H::Point, T)>,
+    number_of_replicas: AtomicUsize,
+    replica_key_format: ReplicaKeyFormat,
+    collision_policy: CollisionPolicy,
+    balanced_placement: bool,
+    min_replica_spacing: H::Point,
+    case_insensitive: bool,
+    key_namespace: Option<FastStr>,
+    hash_seed: u64,
+    collisions: AtomicUsize,
+    /// The positions more than one member hashed to, with how many times a
+    /// member other than the one holding the position collided there.
+    contested: FxDashMap<H::Point, usize>,
+    relocations: AtomicUsize,
+    bulk_depth: AtomicUsize,
+    hits: Option<FxDashMap<FastStr, AtomicU64>>,
+    #[cfg(feature = "hit-rates")]
+    rates: Option<HitRates>,
+    observer: Option<Observer>,
+    hasher: H,
 }
 
-impl Default for Consistent {
+#[cfg(feature = "std")]
+impl<T: Node, H: RingHasher + Default> Default for Consistent<T, H> {
     fn default() -> Self {
-        Self::new()
+        Self::from_hasher(H::default())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Node, H: RingHasher + Default> Consistent<T, H> {
+    /// Builds a ring from `(member, weight)` pairs, giving each member
+    /// `number_of_replicas * weight` virtual nodes and sorting the hashes once.
+    /// As with [`add_weighted`](Self::add_weighted), a weight of zero is
+    /// raised to one.
+    pub fn from_weighted<S: Into<T>>(
+        weights: impl IntoIterator<Item = (S, usize)>,
+        number_of_replicas: usize,
+    ) -> Self {
+        let c = Self::default().with_number_of_replicas(number_of_replicas);
+        {
+            let mut sorted_hashes = c.sorted_hashes.write();
+            for (elt, weight) in weights {
+                c.place(elt.into(), c.number_of_replicas() * weight.max(1));
+            }
+            c.rebuild_sorted_hashes(&mut sorted_hashes);
+        }
+        c
+    }
+
+    /// Builds a ring from `(member, replicas)` pairs, giving each member
+    /// exactly `replicas` virtual nodes whatever the ring's
+    /// [`number_of_replicas`](Self::number_of_replicas), and sorting the
+    /// hashes once. Each member's count is kept, so removing it takes all of
+    /// its virtual nodes. A count of zero is raised to one, as it would leave
+    /// the member unreachable.
+    ///
+    /// The counts stay absolute until the replica count changes:
+    /// [`set_number_of_replicas`](Self::set_number_of_replicas) scales each of
+    /// them by the new replica count over the old one, as it does weighted
+    /// members, keeping at least one, and changing it back restores them.
+    ///
+    /// The member type is not inferred from the pairs, so name the ring's
+    /// type:
+    ///
+    /// ```
+    /// use ringhash::Consistent;
+    ///
+    /// let c: Consistent = Consistent::from_replica_counts([("cacheA", 40), ("cacheB", 10)]);
+    /// assert_eq!(c.virtual_nodes("cacheA").len(), 40);
+    /// ```
+    pub fn from_replica_counts<S: Into<T>>(counts: impl IntoIterator<Item = (S, usize)>) -> Self {
+        let c = Self::default();
+        {
+            let mut sorted_hashes = c.sorted_hashes.write();
+            for (elt, replicas) in counts {
+                c.place(elt.into(), replicas.max(1));
+            }
+            c.rebuild_sorted_hashes(&mut sorted_hashes);
+        }
+        c
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Node, H: RingHasher + Default, S: Into<T>> FromIterator<S> for Consistent<T, H> {
+    fn from_iter<I: IntoIterator<Item = S>>(iter: I) -> Self {
+        let c = Self::default();
+        c.add_many(iter);
+        c
+    }
+}
+
+/// Adds every element, rebuilding the sorted hashes once at the end.
+#[cfg(feature = "std")]
+impl<T: Node, H: RingHasher, S: Into<T>> Extend<S> for &Consistent<T, H> {
+    fn extend<I: IntoIterator<Item = S>>(&mut self, iter: I) {
+        self.add_many(iter);
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Node, H: RingHasher, S: Into<T>> Extend<S> for Consistent<T, H> {
+    fn extend<I: IntoIterator<Item = S>>(&mut self, iter: I) {
+        self.add_many(iter);
+    }
+}
+
+/// Snapshots the ring. The clone is fully independent of the original.
+#[cfg(feature = "std")]
+impl<T: Clone, H: RingHasher + Clone> Clone for Consistent<T, H> {
+    fn clone(&self) -> Self {
+        let sorted_hashes = self.sorted_hashes.lock();
+        Self {
+            circle: self.circle.clone(),
+            members: self.members.clone(),
+            replicas: self.replicas.clone(),
+            weights: self.weights.clone(),
+            disabled: self.disabled.clone(),
+            tags: self.tags.clone(),
+            sorted_hashes: SortedHashes::new(sorted_hashes.to_vec(), sorted_hashes.members()),
+            number_of_replicas: AtomicUsize::new(self.number_of_replicas.load(Ordering::Relaxed)),
+            replica_key_format: self.replica_key_format,
+            collision_policy: self.collision_policy,
+            balanced_placement: self.balanced_placement,
+            min_replica_spacing: self.min_replica_spacing,
+            case_insensitive: self.case_insensitive,
+            key_namespace: self.key_namespace.clone(),
+            hash_seed: self.hash_seed,
+            collisions: AtomicUsize::new(self.collisions.load(Ordering::Relaxed)),
+            contested: self.contested.clone(),
+            relocations: AtomicUsize::new(self.relocations.load(Ordering::Relaxed)),
+            bulk_depth: AtomicUsize::default(),
+            hits: self.hits.as_ref().map(|hits| {
+                hits.iter()
+                    .map(|h| {
+                        (
+                            h.key().to_owned(),
+                            AtomicU64::new(h.load(Ordering::Relaxed)),
+                        )
+                    })
+                    .collect()
+            }),
+            #[cfg(feature = "hit-rates")]
+            rates: self.rates.clone(),
+            observer: self.observer.clone(),
+            hasher: self.hasher.clone(),
+        }
+    }
+}
+
+/// Two rings are equal when they route identically: they have the same
+/// configuration, the same members with the same replica counts, weights,
+/// tags and disabled state, and the same virtual nodes. Weights are compared
+/// as well as replica counts, since members whose weights round to the same
+/// count part ways once the replica count changes. Rings with the same
+/// members can still differ if collisions were resolved differently, e.g.
+/// because members were added in another order under
+/// [`CollisionPolicy::Overwrite`].
+///
+/// The hashers are not compared, so rings whose hashers are configured
+/// differently, e.g. with different seeds, may compare equal.
+#[cfg(feature = "std")]
+impl<T: Node, H: RingHasher> PartialEq for Consistent<T, H> {
+    fn eq(&self, other: &Self) -> bool {
+        if core::ptr::eq(self, other) {
+            return true;
+        }
+        let same_map = |a: &FxDashMap<FastStr, T>, b: &FxDashMap<FastStr, T>| {
+            a.len() == b.len() && a.iter().all(|m| b.get(m.key()).is_some_and(|v| *v == *m))
+        };
+        // Copy one layout rather than holding both locks at once.
+        let sorted_hashes = self.sorted_hashes.lock().to_vec();
+        self.number_of_replicas() == other.number_of_replicas()
+            && self.replica_key_format == other.replica_key_format
+            && self.collision_policy == other.collision_policy
+            && self.balanced_placement == other.balanced_placement
+            && self.min_replica_spacing == other.min_replica_spacing
+            && self.case_insensitive == other.case_insensitive
+            && self.key_namespace == other.key_namespace
+            && self.hash_seed == other.hash_seed
+            && same_map(&self.members, &other.members)
+            && self.replicas.len() == other.replicas.len()
+            && self
+                .replicas
+                .iter()
+                .all(|r| other.replicas.get(r.key()).is_some_and(|v| *v == *r))
+            && self.weights.len() == other.weights.len()
+            && self
+                .weights
+                .iter()
+                .all(|w| other.weights.get(w.key()).is_some_and(|v| *v == *w))
+            && self.disabled.len() == other.disabled.len()
+            && self
+                .disabled
+                .iter()
+                .all(|d| other.disabled.contains(d.key()))
+            && self.tags.len() == other.tags.len()
+            && self
+                .tags
+                .iter()
+                .all(|t| other.tags.get(t.key()).is_some_and(|v| *v == *t))
+            && *other.sorted_hashes.read() == sorted_hashes
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Node, H: RingHasher> Eq for Consistent<T, H> {}
+
+/// Prints a summary rather than the whole circle: the sizes, and the replica
+/// count of up to [`DEBUG_MEMBERS`] members in key order. Use
+/// [`ring_entries`](Consistent::ring_entries) for the full layout.
+#[cfg(feature = "std")]
+impl<T, H: RingHasher> fmt::Debug for Consistent<T, H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut replicas = self
+            .replicas
+            .iter()
+            .map(|r| (r.key().to_owned(), *r.value()))
+            .collect::<Vec<_>>();
+        replicas.sort_unstable();
+        f.debug_struct("Consistent")
+            .field("len", &self.members.len())
+            .field(
+                "number_of_replicas",
+                &self.number_of_replicas.load(Ordering::Relaxed),
+            )
+            .field("virtual_nodes", &self.circle.len())
+            .field("disabled", &self.disabled.len())
+            .field("replicas", &DebugReplicas(&replicas))
+            .finish_non_exhaustive()
+    }
+}
+
+/// How many members the `Debug` output of [`Consistent`] lists.
+#[cfg(feature = "std")]
+const DEBUG_MEMBERS: usize = 20;
+
+#[cfg(feature = "std")]
+struct DebugReplicas<'a>(&'a [(FastStr, usize)]);
+
+#[cfg(feature = "std")]
+impl fmt::Debug for DebugReplicas<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        map.entries(self.0.iter().take(DEBUG_MEMBERS).map(|(k, v)| (k, v)));
+        if self.0.len() > DEBUG_MEMBERS {
+            map.finish_non_exhaustive()
+        } else {
+            map.finish()
+        }
     }
 }
 
+#[cfg(feature = "std")]
 impl Consistent {
     pub fn new() -> Self {
+        Self::with_hasher(FxHasher32)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<H: RingHasher> Consistent<FastStr, H> {
+    pub fn with_hasher(hasher: H) -> Self {
+        Self::from_hasher(hasher)
+    }
+
+    /// Like [`add`](Self::add), but takes the member as bytes. Members are
+    /// identified by string keys, so `elt` must be valid UTF-8.
+    pub fn add_bytes(&self, elt: &[u8]) -> Result<(), Error> {
+        self.add(FastStr::new(core::str::from_utf8(elt)?));
+        Ok(())
+    }
+}
+
+/// A ring over `u64` positions, for fleets large enough that `u32` virtual
+/// nodes start to collide.
+///
+/// ```rust
+/// use ringhash::{Consistent64, FxHasher64};
+///
+/// let c = Consistent64::with_hasher(FxHasher64);
+/// c.add("cacheA");
+/// assert_eq!(c.get("user_mcnulty").unwrap(), "cacheA");
+/// ```
+#[cfg(feature = "std")]
+pub type Consistent64 = Consistent<FastStr, FxHasher64>;
+
+#[cfg(feature = "std")]
+impl<T: Node, H: RingHasher> Consistent<T, H> {
+    fn from_hasher(hasher: H) -> Self {
         Self {
             circle: FxDashMap::default(),
-            members: FxDashSet::default(),
-            sorted_hashes: RwLock::new(Vec::new()),
-            number_of_replicas: 20,
-            count: AtomicUsize::default(),
+            members: FxDashMap::default(),
+            replicas: FxDashMap::default(),
+            weights: FxDashMap::default(),
+            disabled: FxDashSet::default(),
+            tags: FxDashMap::default(),
+            sorted_hashes: SortedHashes::new(Vec::new(), 0),
+            number_of_replicas: AtomicUsize::new(20),
+            replica_key_format: ReplicaKeyFormat::default(),
+            collision_policy: CollisionPolicy::default(),
+            balanced_placement: false,
+            min_replica_spacing: H::Point::ZERO,
+            case_insensitive: false,
+            key_namespace: None,
+            hash_seed: 0,
+            collisions: AtomicUsize::default(),
+            contested: FxDashMap::default(),
+            relocations: AtomicUsize::default(),
+            bulk_depth: AtomicUsize::default(),
+            hits: None,
+            #[cfg(feature = "hit-rates")]
+            rates: None,
+            observer: None,
+            hasher,
         }
     }
 
+    /// Sets how many virtual nodes each member gets per unit of weight.
+    ///
+    /// Zero would leave members with no virtual nodes, so that lookups fail
+    /// with [`Error::EmptyCircle`] despite the ring having members; it is
+    /// raised to one instead.
     pub fn with_number_of_replicas(mut self, number_of_replicas: usize) -> Self {
-        self.number_of_replicas = number_of_replicas;
+        *self.number_of_replicas.get_mut() = number_of_replicas.max(1);
         self
     }
 
-    pub fn add(&self, elt: impl Into<FastStr>) {
-        let elt = elt.into();
-        for i in 0..self.number_of_replicas {
-            self.circle
-                .insert(self.hash_key(&elt_key(&elt, i)), elt.clone());
+    /// Pre-sizes the ring for `members` members, so populating it does not
+    /// reallocate. Room is made for as many virtual nodes as the replica count
+    /// set so far gives, so call this after
+    /// [`with_number_of_replicas`](Self::with_number_of_replicas).
+    ///
+    /// This is meant for a ring that is still empty: existing members are
+    /// dropped.
+    pub fn with_capacity(self, members: usize) -> Self {
+        let vnodes = members * self.number_of_replicas();
+        Self {
+            circle: FxDashMap::with_capacity_and_hasher(vnodes, FxBuildHasher::default()),
+            members: FxDashMap::with_capacity_and_hasher(members, FxBuildHasher::default()),
+            replicas: FxDashMap::with_capacity_and_hasher(members, FxBuildHasher::default()),
+            weights: FxDashMap::with_capacity_and_hasher(members, FxBuildHasher::default()),
+            disabled: FxDashSet::default(),
+            tags: FxDashMap::default(),
+            sorted_hashes: SortedHashes::new(Vec::with_capacity(vnodes), 0),
+            collisions: AtomicUsize::default(),
+            contested: FxDashMap::default(),
+            relocations: AtomicUsize::default(),
+            ..self
         }
-        self.members.insert(elt);
-        self.update_sorted_hashes();
-        self.count.fetch_add(1, Ordering::Relaxed);
     }
 
-    pub fn remove(&self, elt: impl AsRef<str>) {
-        for i in 0..self.number_of_replicas {
-            self.circle
-                .remove(&self.hash_key(&elt_key(elt.as_ref(), i)));
-        }
-        self.members.remove(elt.as_ref());
-        self.update_sorted_hashes();
-        self.count.fetch_sub(1, Ordering::Relaxed);
-    }
-
-    pub fn set(&self, elts: Vec<impl Into<FastStr>>) {
-        let elts = elts.into_iter().map(|elt| elt.into()).collect::<Vec<_>>();
-        let mut keys = Vec::with_capacity(self.members().len());
-        for member in self.members.iter() {
-            let mut found = false;
-            for elt in elts.iter() {
-                if member.key() == elt {
-                    found = true;
-                    break;
+    /// Makes room in the sorted hashes for `additional_members` more members'
+    /// virtual nodes, so adding them does not reallocate.
+    pub fn reserve(&self, additional_members: usize) {
+        self.sorted_hashes
+            .write()
+            .reserve(additional_members * self.number_of_replicas());
+    }
+
+    /// Runs `f` on the ring with the sorted hashes kept at their capacity:
+    /// rebuilds inside the scope never shrink them, so churn from adding and
+    /// removing members in quick succession does not reallocate back and
+    /// forth. Spare capacity is given back once the outermost scope ends.
+    ///
+    /// With the `arc-swap` feature every change copies the sorted hashes
+    /// anyway, so this and [`reserve`](Self::reserve) save nothing.
+    pub fn with_bulk<R>(&self, f: impl FnOnce(&Self) -> R) -> R {
+        struct Bulk<'a, T: Node, H: RingHasher>(&'a Consistent<T, H>);
+
+        impl<T: Node, H: RingHasher> Drop for Bulk<'_, T, H> {
+            fn drop(&mut self) {
+                let mut sorted_hashes = self.0.sorted_hashes.write();
+                if self.0.bulk_depth.fetch_sub(1, Ordering::Relaxed) == 1 {
+                    self.0.shrink_sorted_hashes(&mut sorted_hashes);
                 }
             }
-            if !found {
-                keys.push(member.key().to_owned());
-            }
         }
 
-        for key in keys {
-            self.remove(key);
-        }
+        self.bulk_depth.fetch_add(1, Ordering::Relaxed);
+        let _bulk = Bulk(self);
+        f(self)
+    }
+
+    /// Counts how many times each member is picked by
+    /// [`get_counting`](Self::get_counting). Rings built without this pay
+    /// nothing for the counters.
+    pub fn with_hit_counting(mut self) -> Self {
+        self.hits = Some(FxDashMap::default());
+        self
+    }
+
+    pub fn with_replica_key_format(mut self, replica_key_format: ReplicaKeyFormat) -> Self {
+        self.replica_key_format = replica_key_format;
+        self
+    }
+
+    /// Reports changes and lookups to `observer`. Rings built without one pay
+    /// nothing for the hooks.
+    pub fn with_observer(mut self, observer: Arc<dyn RingObserver>) -> Self {
+        self.observer = Some(Observer(observer));
+        self
+    }
+
+    pub fn with_collision_policy(mut self, collision_policy: CollisionPolicy) -> Self {
+        self.collision_policy = collision_policy;
+        self
+    }
+
+    /// Places each virtual node at whichever of its first four probe
+    /// positions splits the gap it lands in most
+    /// evenly, rather than at the first free one. This evens out the arc
+    /// lengths, and so the members' shares of the keyspace.
+    ///
+    /// Only applies under [`CollisionPolicy::Probe`]. Placement then depends
+    /// on the rest of the circle, so each added member costs a sort of the
+    /// circle, and rings routing alike must be built in the same order.
+    pub fn with_balanced_placement(mut self, balanced_placement: bool) -> Self {
+        self.balanced_placement = balanced_placement;
+        self
+    }
+
+    /// Keeps each member's virtual nodes at least `min_replica_spacing` apart
+    /// around the circle. A replica whose probe position falls closer than
+    /// that to one of its siblings moves on to the next free probe position,
+    /// as if it had collided; the arc between two nearby siblings would
+    /// route to the same member anyway. If every probe position is too close,
+    /// the first free one is kept. [`relocation_count`](Self::relocation_count)
+    /// tells how many replicas moved.
+    ///
+    /// Only applies under [`CollisionPolicy::Probe`]. Zero, the default,
+    /// disables the check.
+    pub fn with_min_replica_spacing(mut self, min_replica_spacing: H::Point) -> Self {
+        self.min_replica_spacing = min_replica_spacing;
+        self
+    }
+
+    /// Lowercases lookup keys before hashing them, so that e.g. `"User"` and
+    /// `"user"` route to the same member. Keys that are not valid UTF-8 are
+    /// lowercased as ASCII.
+    ///
+    /// Off by default. When on, every lookup pays for lowercasing a copy of
+    /// its key. Member keys, and so the virtual nodes, stay case-sensitive.
+    pub fn with_case_insensitive(mut self, case_insensitive: bool) -> Self {
+        self.case_insensitive = case_insensitive;
+        self
+    }
+
+    /// Prepends `key_namespace` to every lookup key before hashing it, so
+    /// rings sharing members but in different namespaces, e.g. one per
+    /// tenant, route the same key independently. Member keys, and so the
+    /// virtual nodes, are left as they are. An empty namespace, the default,
+    /// leaves lookup keys unchanged.
+    pub fn with_key_namespace(mut self, key_namespace: impl Into<FastStr>) -> Self {
+        let key_namespace = key_namespace.into();
+        self.key_namespace = (!key_namespace.is_empty()).then_some(key_namespace);
+        self
+    }
+
+    /// Salts every hashed key, both virtual-node keys and lookup keys, with
+    /// `seed`, so rings with different seeds place and route independently.
+    /// A seed of `0`, the default, leaves keys unsalted.
+    pub fn with_hash_seed(mut self, hash_seed: u64) -> Self {
+        self.hash_seed = hash_seed;
+        self
+    }
+
+    pub fn number_of_replicas(&self) -> usize {
+        self.number_of_replicas.load(Ordering::Relaxed)
+    }
 
-        for v in elts.into_iter() {
-            if !self.members.contains(&v) {
-                self.add(v);
+    /// Changes the replica count of a live ring: each member gets
+    /// `number_of_replicas` times its weight in virtual nodes, rounded and at
+    /// least one. Weights are kept as given, so scaling down and back up
+    /// restores every member's count.
+    ///
+    /// A virtual node's position only depends on its member and replica index,
+    /// so only the difference is placed or removed: existing virtual nodes stay
+    /// put, and only keys on the arcs of added or removed ones move.
+    ///
+    /// As with [`with_number_of_replicas`](Self::with_number_of_replicas),
+    /// zero is raised to one.
+    pub fn set_number_of_replicas(&self, number_of_replicas: usize) {
+        let number_of_replicas = number_of_replicas.max(1);
+        let mut sorted_hashes = self.sorted_hashes.write();
+        let prev = self
+            .number_of_replicas
+            .swap(number_of_replicas, Ordering::Relaxed);
+        let members = self
+            .members
+            .iter()
+            .map(|m| {
+                let replicas = self.replicas.get(m.key()).map_or(0, |r| *r);
+                let weight = self
+                    .weights
+                    .get(m.key())
+                    .map_or(replicas as f64 / prev as f64, |w| *w);
+                (m.key().to_owned(), m.value().to_owned(), replicas, weight)
+            })
+            .collect::<Vec<_>>();
+        for (key, elt, replicas, weight) in members {
+            let target = self.replicas_for(weight);
+            if target > replicas {
+                self.place_replicas(&elt, &key, replicas..target);
+            } else {
+                self.unplace_replicas(&elt, &key, target..replicas);
             }
+            self.replicas.insert(key, target);
         }
+        self.rebuild_sorted_hashes(&mut sorted_hashes);
     }
 
-    pub fn members(&self) -> Vec<FastStr> {
-        self.members
-            .iter()
-            .map(|member| member.key().to_owned())
+    pub fn add(&self, elt: impl Into<T>) {
+        self.add_weighted(elt, 1);
+    }
+
+    /// Adds `elt` with `number_of_replicas * weight` virtual nodes, so its share
+    /// of the keyspace grows proportionally to `weight`.
+    ///
+    /// A weight of zero would leave `elt` a member that no key routes to, so
+    /// that lookups could fail with [`Error::EmptyCircle`] on a ring with
+    /// members; it is raised to one instead.
+    pub fn add_weighted(&self, elt: impl Into<T>, weight: usize) {
+        let weight = weight.max(1);
+        self.add_replicas(
+            elt.into(),
+            self.number_of_replicas() * weight,
+            weight as f64,
+        );
+    }
+
+    /// Like [`add_weighted`](Self::add_weighted), but takes the weight as an
+    /// operator would write it: a multiplier such as `"4"`, `"4x"` or `"0.5"`,
+    /// or a percentage such as `"200%"`. The member gets
+    /// `number_of_replicas * weight` virtual nodes, rounded, and at least one.
+    /// The weight itself is kept, so
+    /// [`set_number_of_replicas`](Self::set_number_of_replicas) rescales from
+    /// it rather than from the rounded count. Errors with
+    /// [`Error::InvalidWeight`] if the spec does not parse as a positive
+    /// weight.
+    pub fn add_weighted_str(&self, elt: impl Into<T>, weight_spec: &str) -> Result<(), Error> {
+        let weight = parse_weight(weight_spec)?;
+        let replicas = self.replicas_for(weight);
+        self.add_replicas(elt.into(), replicas, weight);
+        Ok(())
+    }
+
+    /// Like [`add`](Self::add), but returns the ring positions whose owner
+    /// changed, in ascending order, e.g. to update a routing table mirroring
+    /// the ring without diffing it. After the call, each of them is either
+    /// held by `elt` or, if `elt` was already a member, one of its old
+    /// virtual nodes that is no longer on the circle.
+    pub fn add_reporting(&self, elt: impl Into<T>) -> Vec<H::Point> {
+        let (removed, added) = self.add_replicas(elt.into(), self.number_of_replicas(), 1.0);
+        // A position in both was vacated and then taken again by `elt`.
+        let mut changed = [removed, added].concat();
+        changed.sort_unstable();
+        changed
+            .chunk_by(|a, b| a == b)
+            .filter(|same| same.len() == 1)
+            .map(|same| same[0])
             .collect()
     }
 
-    pub fn get(&self, name: impl AsRef<str>) -> Result<FastStr, Error> {
-        if self.circle.is_empty() {
-            return Err(Error::EmptyCircle);
+    /// Adds `elt` with `replicas` virtual nodes and `weight`, returning the
+    /// hashes of its virtual nodes removed and added.
+    fn add_replicas(&self, elt: T, replicas: usize, weight: f64) -> (Vec<H::Point>, Vec<H::Point>) {
+        let mut sorted_hashes = self.sorted_hashes.write();
+        let (removed, added) = self.place_weighted(elt.clone(), replicas, weight);
+        for hash in &removed {
+            remove_sorted(&mut sorted_hashes, *hash);
         }
-        let key = self.hash_key(name.as_ref());
-        let i = self.search(key);
-        Ok(self
-            .circle
-            .get(&self.sorted_hashes.read()[i])
-            .unwrap()
-            .to_owned())
+        for hash in &added {
+            insert_sorted(&mut sorted_hashes, *hash, elt.clone());
+        }
+        (removed, added)
     }
 
-    pub fn get_two(&self, name: impl AsRef<str>) -> Result<(FastStr, FastStr), Error> {
-        if self.circle.is_empty() {
-            return Err(Error::EmptyCircle);
+    /// Like [`add`](Self::add), but also tags `elt` with `tag`, e.g. its rack
+    /// or zone, for [`get_n_distinct_tags`](Self::get_n_distinct_tags).
+    /// Re-adding a member replaces its tag, and adding it untagged drops it.
+    pub fn add_tagged(&self, elt: impl Into<T>, tag: impl Into<FastStr>) {
+        let elt = elt.into();
+        let mut sorted_hashes = self.sorted_hashes.write();
+        let (removed, added) = self.place(elt.clone(), self.number_of_replicas());
+        self.tags.insert(elt.node_key(), tag.into());
+        for hash in removed {
+            remove_sorted(&mut sorted_hashes, hash);
         }
-        let key = self.hash_key(name.as_ref());
-        let i = self.search(key);
-        let a = self
-            .circle
-            .get(&self.sorted_hashes.read()[i])
-            .unwrap()
-            .to_owned();
-        let mut b = "".into();
-        if self.count.load(Ordering::Relaxed) == 1 {
-            return Ok((a, b));
+        for hash in added {
+            insert_sorted(&mut sorted_hashes, hash, elt.clone());
         }
-        let mut j = i + 1;
-        let sorted_hashes = self.sorted_hashes.read();
-        while j != i {
-            if j >= sorted_hashes.len() {
-                j = 0;
-            }
-            let v = self.circle.get(&sorted_hashes[j]).unwrap();
-            if !a.eq(v.value()) {
-                b = v.value().to_owned();
-                break;
-            }
-            j += 1;
+    }
+
+    /// Returns the tag `elt` was added with by
+    /// [`add_tagged`](Self::add_tagged), if any.
+    pub fn tag(&self, elt: impl AsRef<str>) -> Option<FastStr> {
+        self.tags.get(elt.as_ref()).map(|t| t.value().to_owned())
+    }
+
+    /// Like [`add`](Self::add), but leaves the ring untouched if a member with
+    /// the same key is already present. Returns whether `elt` was added.
+    pub fn add_if_absent(&self, elt: impl Into<T>) -> bool {
+        let elt = elt.into();
+        let mut sorted_hashes = self.sorted_hashes.write();
+        if self.members.contains_key(&elt.node_key()) {
+            return false;
         }
-        Ok((a, b))
+        let (_, added) = self.place(elt.clone(), self.number_of_replicas());
+        for hash in added {
+            insert_sorted(&mut sorted_hashes, hash, elt.clone());
+        }
+        true
     }
 
-    pub fn get_n(&self, name: impl AsRef<str>, mut n: usize) -> Result<Vec<FastStr>, Error> {
-        if self.circle.is_empty() {
-            return Err(Error::EmptyCircle);
+    /// Adds all of `elts`, rebuilding the sorted hashes once at the end rather
+    /// than once per member.
+    pub fn add_many(&self, elts: impl IntoIterator<Item = impl Into<T>>) {
+        let mut sorted_hashes = self.sorted_hashes.write();
+        for elt in elts {
+            self.place(elt.into(), self.number_of_replicas());
         }
-        let count = self.count.load(Ordering::Relaxed);
-        if count < n {
-            n = count;
+        self.rebuild_sorted_hashes(&mut sorted_hashes);
+    }
+
+    pub fn remove(&self, elt: impl AsRef<str>) {
+        let _ = self.try_remove(elt);
+    }
+
+    /// Like [`remove`](Self::remove), but returns the ring positions of the
+    /// virtual nodes removed, in ascending order. Unknown members remove
+    /// nothing.
+    pub fn remove_reporting(&self, elt: impl AsRef<str>) -> Vec<H::Point> {
+        let elt = elt.as_ref();
+        let mut sorted_hashes = self.sorted_hashes.write();
+        if !self.members.contains_key(elt) {
+            return Vec::new();
         }
-        let key = self.hash_key(name.as_ref());
-        let i = self.search(key);
-        let mut res = Vec::with_capacity(n);
-        let sorted_hashes = self.sorted_hashes.read();
-        res.push(
-            self.circle
-                .get(&sorted_hashes[i])
-                .unwrap()
-                .value()
-                .to_owned(),
-        );
-        if n == 1 {
-            return Ok(res);
+        let mut removed = self.unplace(elt);
+        for hash in &removed {
+            remove_sorted(&mut sorted_hashes, *hash);
         }
-        let mut j = i + 1;
-        while j != i {
-            if j >= sorted_hashes.len() {
-                j = 0;
-            }
-            let v = self.circle.get(&sorted_hashes[j]).unwrap();
-            if !slice_contains_member(&res, v.value()) {
-                res.push(v.value().to_owned());
-            }
-            if res.len() == n {
-                break;
+        removed.sort_unstable();
+        removed
+    }
+
+    /// Removes all of `elts` under a single write lock, rebuilding the sorted
+    /// hashes once at the end rather than once per member. Unknown members
+    /// are ignored, and if none of `elts` is a member nothing is rebuilt.
+    pub fn remove_many(&self, elts: impl IntoIterator<Item = impl AsRef<str>>) {
+        let mut sorted_hashes = self.sorted_hashes.write();
+        let mut removed = false;
+        for elt in elts {
+            let elt = elt.as_ref();
+            if self.members.contains_key(elt) {
+                self.unplace(elt);
+                removed = true;
             }
-            j += 1;
         }
-        Ok(res)
+        if removed {
+            self.rebuild_sorted_hashes(&mut sorted_hashes);
+        }
     }
 
-    fn search(&self, key: u32) -> usize {
-        let sorted_hashes = self.sorted_hashes.read();
-        let i = sorted_hashes.partition_point(|x| *x <= key);
-        if i >= sorted_hashes.len() {
-            0
-        } else {
-            i
+    /// Removes every member whose key matches `pred` under a single write
+    /// lock, rebuilding the sorted hashes once, and returns how many were
+    /// removed.
+    pub fn remove_where<F: Fn(&str) -> bool>(&self, pred: F) -> usize {
+        let mut sorted_hashes = self.sorted_hashes.write();
+        let matching = self
+            .members
+            .iter()
+            .filter(|member| pred(member.key()))
+            .map(|member| member.key().to_owned())
+            .collect::<Vec<_>>();
+        for key in &matching {
+            self.unplace(key);
         }
+        if !matching.is_empty() {
+            self.rebuild_sorted_hashes(&mut sorted_hashes);
+        }
+        matching.len()
     }
 
-    fn hash_key(&self, key: &str) -> u32 {
-        fxhash::hash32(key)
+    /// Like [`remove`](Self::remove), but takes the member key as bytes. Bytes
+    /// that are not valid UTF-8 can't name a member and are ignored.
+    pub fn remove_bytes(&self, elt: &[u8]) {
+        if let Ok(elt) = core::str::from_utf8(elt) {
+            self.remove(elt);
+        }
     }
 
-    fn update_sorted_hashes(&self) {
+    /// Like [`remove`](Self::remove), but errors with
+    /// [`Error::MemberNotFound`] if `elt` is not a member.
+    pub fn try_remove(&self, elt: impl AsRef<str>) -> Result<(), Error> {
+        let elt = elt.as_ref();
         let mut sorted_hashes = self.sorted_hashes.write();
-        sorted_hashes.clear();
+        if !self.members.contains_key(elt) {
+            return Err(Error::MemberNotFound(FastStr::new(elt)));
+        }
+        for hash in self.unplace(elt) {
+            remove_sorted(&mut sorted_hashes, hash);
+        }
+        Ok(())
+    }
 
-        if sorted_hashes.capacity() / (self.number_of_replicas * 4) > self.circle.len() {
-            sorted_hashes.shrink_to(self.circle.len());
+    /// Swaps `old` for `new` under a single write lock, so concurrent lookups
+    /// never see the ring without either of them. `new` takes over the replica
+    /// count of `old`; if `old` is not a member, `new` is simply added.
+    pub fn replace(&self, old: impl AsRef<str>, new: impl Into<T>) {
+        let old = old.as_ref();
+        let mut sorted_hashes = self.sorted_hashes.write();
+        let replicas = self
+            .replicas
+            .get(old)
+            .map_or(self.number_of_replicas(), |r| *r);
+        let weight = self.weights.get(old).map_or(1.0, |w| *w);
+        let new = new.into();
+        let mut removed = self.unplace(old);
+        let (dropped, added) = self.place_weighted(new.clone(), replicas, weight);
+        removed.extend(dropped);
+        for hash in removed {
+            remove_sorted(&mut sorted_hashes, hash);
         }
-        for k in self.circle.iter() {
-            sorted_hashes.push(*k.key());
+        for hash in added {
+            insert_sorted(&mut sorted_hashes, hash, new.clone());
         }
-        sorted_hashes.sort();
     }
-}
 
-fn elt_key(elt: &str, idx: usize) -> String {
-    format!("{}{}", idx, elt)
-}
+    /// Makes the members exactly `elts`: members missing from it are removed
+    /// and new ones added, while members already present keep their virtual
+    /// nodes. All changes are applied under a single write lock with one
+    /// rebuild of the sorted hashes, so lookups see either the old or the new
+    /// member set.
+    pub fn set(&self, elts: Vec<impl Into<T>>) {
+        let elts = elts
+            .into_iter()
+            .map(|elt| {
+                let elt = elt.into();
+                (elt.node_key(), elt)
+            })
+            .collect::<Vec<_>>();
+        let keep = elts.iter().map(|(key, _)| key).collect::<FxHashSet<_>>();
+        let mut sorted_hashes = self.sorted_hashes.write();
+        let stale = self
+            .members
+            .iter()
+            .filter(|member| !keep.contains(member.key()))
+            .map(|member| member.key().to_owned())
+            .collect::<Vec<_>>();
+        let mut changed = !stale.is_empty();
+        for key in stale {
+            self.unplace(&key);
+        }
 
-fn slice_contains_member(set: &[FastStr], member: &str) -> bool {
-    for m in set.iter() {
-        if m == member {
-            return true;
+        for (key, v) in elts.into_iter() {
+            if !self.members.contains_key(&key) {
+                self.place(v, self.number_of_replicas());
+                changed = true;
+            }
+        }
+        if changed {
+            self.rebuild_sorted_hashes(&mut sorted_hashes);
         }
     }
-    false
-}
 
-#[derive(thiserror::Error, Debug)]
-pub enum Error {
-    #[error("empty circle")]
-    EmptyCircle,
-}
+    /// Removes every member from the ring.
+    pub fn clear(&self) {
+        let mut sorted_hashes = self.sorted_hashes.write();
+        if let Some(observer) = &self.observer {
+            for member in self.members.iter() {
+                observer.0.on_remove(member.key());
+            }
+        }
+        self.circle.clear();
+        self.members.clear();
+        self.replicas.clear();
+        self.weights.clear();
+        self.disabled.clear();
+        self.tags.clear();
+        sorted_hashes.clear();
+        self.sorted_hashes.set_members(0);
+        self.collisions.store(0, Ordering::Relaxed);
+        self.contested.clear();
+        self.relocations.store(0, Ordering::Relaxed);
+    }
 
-#[cfg(test)]
-mod tests {
-    use crate::Consistent;
+    /// Recomputes every virtual node from the members and their replica
+    /// counts, placing the members in key order, and reports on the result.
+    /// The collision and relocation counts restart from this placement.
+    ///
+    /// With no collisions this reproduces the current layout. Otherwise
+    /// contested slots are settled afresh, which undoes evictions under
+    /// [`CollisionPolicy::Overwrite`] and can move a few keys.
+    pub fn rebuild(&self) -> RebuildReport {
+        let mut sorted_hashes = self.sorted_hashes.write();
+        let mut members = self
+            .members
+            .iter()
+            .map(|m| {
+                let replicas = self.replicas.get(m.key()).map_or(0, |r| *r);
+                (m.key().to_owned(), m.value().to_owned(), replicas)
+            })
+            .collect::<Vec<_>>();
+        members.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        self.circle.clear();
+        self.collisions.store(0, Ordering::Relaxed);
+        self.contested.clear();
+        self.relocations.store(0, Ordering::Relaxed);
+        for (key, elt, replicas) in &members {
+            self.place_replicas(elt, key, 0..*replicas);
+        }
+        self.rebuild_sorted_hashes(&mut sorted_hashes);
+        RebuildReport {
+            virtual_nodes: sorted_hashes.len(),
+            collisions: self.collisions.load(Ordering::Relaxed),
+            relocations: self.relocations.load(Ordering::Relaxed),
+            members: members.len(),
+        }
+    }
 
-    #[test]
-    fn test_add() {
-        let c = Consistent::new();
-        c.add("abcdefg");
-        assert_eq!(c.circle.len(), 20);
-        assert_eq!(c.sorted_hashes.read().len(), 20);
-        c.add("qwer");
-        assert_eq!(c.circle.len(), 40);
-        assert_eq!(c.sorted_hashes.read().len(), 40);
+    /// Drops every virtual node that does not belong to a current member and
+    /// makes the sorted hashes mirror the circle again, returning how many
+    /// stale entries were removed. Live virtual nodes stay where they are, so
+    /// no key moves, unlike with [`rebuild`](Self::rebuild).
+    ///
+    /// The ring keeps its layout in step with the members by itself; this is a
+    /// repair for one that drifted anyway, e.g. after a bug or a bad load.
+    pub fn compact(&self) -> usize {
+        let mut sorted_hashes = self.sorted_hashes.write();
+        let mut stale = sorted_hashes
+            .iter()
+            .filter(|(hash, v)| self.circle.get(hash).is_none_or(|c| *c != *v))
+            .map(|(hash, _)| *hash)
+            .collect::<FxHashSet<_>>();
+        self.circle.retain(|hash, v| {
+            let live = self.members.get(&v.node_key()).is_some_and(|m| *m == *v);
+            if !live {
+                stale.insert(*hash);
+                self.contested.remove(hash);
+            }
+            live
+        });
+        self.rebuild_sorted_hashes(&mut sorted_hashes);
+        stale.len()
     }
 
-    #[test]
-    fn test_remove() {
-        let c = Consistent::new();
-        c.add("abcdefg");
-        c.remove("abcdefg");
-        assert_eq!(c.circle.len(), 0);
-        assert_eq!(c.sorted_hashes.read().len(), 0);
+    /// Returns the members in no particular order, which may differ between
+    /// calls; see [`members_sorted`](Self::members_sorted) for a stable one.
+    pub fn members(&self) -> Vec<T> {
+        self.members_iter().collect()
     }
 
-    #[test]
-    fn test_remove_non_existing() {
-        let c = Consistent::new();
-        c.add("abcdefg");
-        c.remove("abcdefghijk");
-        assert_eq!(c.circle.len(), 20);
+    /// Returns the members ordered by member key, e.g. for snapshot tests or
+    /// listing nodes to operators.
+    pub fn members_sorted(&self) -> Vec<T> {
+        let mut members = self.members();
+        members.sort_by_cached_key(|member| member.node_key());
+        members
     }
 
-    #[test]
-    fn test_get_empty() {
-        let c = Consistent::new();
-        let res = c.get("asdfsadfsadf");
-        assert!(res.is_err());
+    /// Yields the members in no particular order, cloning each one lazily.
+    ///
+    /// The iterator holds read locks on parts of the member map until it is
+    /// dropped, so the ring must not be mutated while iterating.
+    pub fn members_iter(&self) -> impl Iterator<Item = T> + '_ {
+        self.members.iter().map(|member| member.value().to_owned())
     }
 
-    #[test]
-    fn test_get_single() {
-        let c = Consistent::new();
-        c.add("abcdefg");
-        let res = c.get("asdfsadfsadf");
-        assert!(res.is_ok());
-        assert_eq!(res.unwrap(), "abcdefg");
+    /// Compares the member sets of two rings by member key: `added` holds the
+    /// members of `other` missing from `self`, and `removed` those of `self`
+    /// missing from `other`, each ordered by key.
+    pub fn diff<H2: RingHasher>(&self, other: &Consistent<T, H2>) -> RingDiff<T> {
+        let missing = |from: &FxDashMap<FastStr, T>, to: &FxDashMap<FastStr, T>| {
+            let mut res = from
+                .iter()
+                .filter(|m| !to.contains_key(m.key()))
+                .map(|m| (m.key().to_owned(), m.value().to_owned()))
+                .collect::<Vec<_>>();
+            res.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+            res.into_iter().map(|(_, v)| v).collect()
+        };
+        RingDiff {
+            added: missing(&other.members, &self.members),
+            removed: missing(&self.members, &other.members),
+        }
     }
 
-    #[test]
-    fn test_get_two() {
-        let c = Consistent::new();
-        c.add("abcdefg");
-        c.add("opqrstu");
-        let res = c.get_two("asdfsadfsadf");
-        assert!(res.is_ok());
+    /// Returns the number of members, not virtual nodes.
+    pub fn len(&self) -> usize {
+        self.sorted_hashes.members()
     }
 
-    #[test]
-    fn test_get_n() {
-        let c = Consistent::new();
-        c.add("abcdefg");
-        c.add("opqrstu");
-        c.add("hijklmn");
-        let res = c.get_n("asdfsadfsadf", 3);
-        assert!(res.is_ok());
-        let mut res = res.unwrap();
-        res.sort();
-        assert_eq!(res, vec!["abcdefg", "hijklmn", "opqrstu"]);
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
-    #[test]
-    fn test_get_n_more_than_available() {
-        let c = Consistent::new();
-        c.add("abcdefg");
-        c.add("opqrstu");
-        c.add("hijklmn");
-        let res = c.get_n("asdfsadfsadf", 4);
-        assert!(res.is_ok());
-        let mut res = res.unwrap();
-        res.sort();
-        assert_eq!(res, vec!["abcdefg", "hijklmn", "opqrstu"]);
+
+    /// Returns how many times a virtual node landed on a slot already taken by
+    /// another one. What happens then depends on the [`CollisionPolicy`].
+    pub fn collision_count(&self) -> usize {
+        self.collisions.load(Ordering::Relaxed)
+    }
+
+    /// Returns how many virtual nodes were placed away from where they would
+    /// otherwise have gone, to keep them
+    /// [`min_replica_spacing`](Self::with_min_replica_spacing) away from
+    /// their siblings.
+    pub fn relocation_count(&self) -> usize {
+        self.relocations.load(Ordering::Relaxed)
+    }
+
+    /// Returns how many virtual nodes are on the circle. Collisions given up
+    /// on leave this below the sum of the members' replica counts.
+    pub fn virtual_node_count(&self) -> usize {
+        self.circle.len()
+    }
+
+    pub fn contains(&self, elt: impl AsRef<str>) -> bool {
+        self.members.contains_key(elt.as_ref())
+    }
+
+    pub fn get(&self, name: impl AsRef<str>) -> Result<T, Error> {
+        self.get_bytes(name.as_ref().as_bytes())
+    }
+
+    /// Like [`get`](Self::get), but for binary keys. A key routes the same
+    /// whether it is passed as `&str` or as its bytes.
+    pub fn get_bytes(&self, name: &[u8]) -> Result<T, Error> {
+        self.find(name, |_| true)
+    }
+
+    /// Routes a batch of keys, returning their members in input order. The
+    /// keys are hashed up front and then looked up under a single read lock,
+    /// rather than locking once per key as repeated [`get`](Self::get)s would.
+    ///
+    /// Errors with [`Error::EmptyCircle`] if no member can take the keys.
+    pub fn get_many(&self, names: &[impl AsRef<str>]) -> Result<Vec<T>, Error> {
+        let keys = names
+            .iter()
+            .map(|name| self.lookup_key(name.as_ref().as_bytes()))
+            .collect::<Vec<_>>();
+        let sorted_hashes = self.sorted_hashes.read();
+        if sorted_hashes.is_empty() {
+            return Err(Error::EmptyCircle);
+        }
+        names
+            .iter()
+            .zip(keys)
+            .map(|(name, key)| {
+                let name = name.as_ref().as_bytes();
+                self.find_map_in(&sorted_hashes, key, Some(name), |_| true, T::clone)
+            })
+            .collect()
+    }
+
+    /// Like [`get`](Self::get), but returns `default` instead of failing when
+    /// no member can take `name`, e.g. before any member has been added.
+    pub fn get_or(&self, name: impl AsRef<str>, default: impl Into<T>) -> T {
+        self.get(name).unwrap_or_else(|_| default.into())
+    }
+
+    /// Like [`get`](Self::get), but never blocks: returns `Ok(None)` if a
+    /// writer holds or is waiting for the lock, so the caller can retry or
+    /// fall back instead of parking the thread, e.g. an async worker. With the
+    /// `arc-swap` feature lookups never wait, and this always returns a
+    /// member.
+    pub fn try_get(&self, name: impl AsRef<str>) -> Result<Option<T>, Error> {
+        let name = name.as_ref().as_bytes();
+        let key = self.lookup_key(name);
+        let Some(sorted_hashes) = self.sorted_hashes.try_read() else {
+            return Ok(None);
+        };
+        self.find_map_in(&sorted_hashes, key, Some(name), |_| true, T::clone)
+            .map(Some)
+    }
+
+    /// Like [`get`](Self::get), but takes the already hashed key, e.g. to reuse
+    /// a hash computed elsewhere or to route by a custom hashing scheme.
+    /// `key_hash` is used as is, so it should come from the ring's hasher to
+    /// agree with [`get`](Self::get); the hash seed is not applied. The
+    /// observer is not notified, as there is no key to report.
+    pub fn get_by_hash(&self, key_hash: H::Point) -> Result<T, Error> {
+        self.find_map_at(key_hash, None, |_| true, T::clone)
+    }
+
+    /// Like [`get`](Self::get), but skips the members whose key is in
+    /// `exclude`, e.g. nodes known to be down for this request. Errors with
+    /// [`Error::EmptyCircle`] if every member is excluded.
+    pub fn get_excluding<S: BuildHasher>(
+        &self,
+        name: impl AsRef<str>,
+        exclude: &HashSet<FastStr, S>,
+    ) -> Result<T, Error> {
+        if exclude.is_empty() {
+            return self.get(name);
+        }
+        self.find(name.as_ref().as_bytes(), |v| {
+            !exclude.contains(&v.node_key())
+        })
+    }
+
+    /// Like [`get`](Self::get), but skips the members for which `is_healthy`
+    /// returns false, e.g. consulting a live health checker. Errors with
+    /// [`Error::EmptyCircle`] if no member qualifies.
+    pub fn get_where<F: Fn(&T) -> bool>(
+        &self,
+        name: impl AsRef<str>,
+        is_healthy: F,
+    ) -> Result<T, Error> {
+        self.find(name.as_ref().as_bytes(), is_healthy)
+    }
+
+    /// Like [`get`](Self::get), but passes the member to `f` in place instead
+    /// of cloning it out of the ring.
+    ///
+    /// `f` runs while the read lock on the sorted hashes is held, as with
+    /// [`get_ref`](Self::get_ref): changing the ring from `f` deadlocks, and
+    /// so can a lookup from `f` once a writer is queued. With the `arc-swap`
+    /// feature no lock is held, only the layout the member was found in is
+    /// pinned, so `f` may call back into the ring either way.
+    pub fn get_with<R>(&self, name: impl AsRef<str>, f: impl FnOnce(&T) -> R) -> Result<R, Error> {
+        self.find_map(name.as_ref().as_bytes(), |_| true, f)
+    }
+
+    /// Like [`get`](Self::get), but borrows the member from the ring instead
+    /// of cloning it, for tight lookup loops over a ring that isn't changing.
+    ///
+    /// The returned [`MemberRef`] holds the read lock on the sorted hashes
+    /// until it is dropped, so writers wait for it: adding or removing a
+    /// member on the same thread while holding it deadlocks, and so can
+    /// another lookup on the same thread once a writer is queued. Drop it
+    /// before touching the ring again. With the `arc-swap` feature it instead
+    /// pins the layout it was found in, so writers proceed and it may outlive
+    /// the member's removal, but holding many for long slows down lookups.
+    pub fn get_ref(&self, name: impl AsRef<str>) -> Result<MemberRef<'_, T, H::Point>, Error> {
+        let name = name.as_ref().as_bytes();
+        let key = self.lookup_key(name);
+        let sorted_hashes = self.sorted_hashes.read();
+        let i = self.find_index_in(&sorted_hashes, key, Some(name), |_| true)?;
+        Ok(MemberRef::new(sorted_hashes, i))
+    }
+
+    /// Returns the first enabled member clockwise from `name` that satisfies
+    /// `f`.
+    fn find(&self, name: &[u8], f: impl Fn(&T) -> bool) -> Result<T, Error> {
+        self.find_map(name, f, T::clone)
+    }
+
+    fn find_map<R>(
+        &self,
+        name: &[u8],
+        f: impl Fn(&T) -> bool,
+        map: impl FnOnce(&T) -> R,
+    ) -> Result<R, Error> {
+        self.find_map_at(self.lookup_key(name), Some(name), f, map)
+    }
+
+    /// Like [`find_map`](Self::find_map), but starts from the ring position
+    /// `key`. The observer is only told about lookups of a known `name`.
+    fn find_map_at<R>(
+        &self,
+        key: H::Point,
+        name: Option<&[u8]>,
+        f: impl Fn(&T) -> bool,
+        map: impl FnOnce(&T) -> R,
+    ) -> Result<R, Error> {
+        self.find_map_in(&self.sorted_hashes.read(), key, name, f, map)
+    }
+
+    /// Like [`find_map_at`](Self::find_map_at), over the already locked
+    /// `sorted_hashes`.
+    fn find_map_in<R>(
+        &self,
+        sorted_hashes: &[(H::Point, T)],
+        key: H::Point,
+        name: Option<&[u8]>,
+        f: impl Fn(&T) -> bool,
+        map: impl FnOnce(&T) -> R,
+    ) -> Result<R, Error> {
+        let j = self.find_index_in(sorted_hashes, key, name, f)?;
+        Ok(map(&sorted_hashes[j].1))
+    }
+
+    /// Like [`find_map_in`](Self::find_map_in), but returns the index of the
+    /// member's entry in `sorted_hashes`.
+    fn find_index_in(
+        &self,
+        sorted_hashes: &[(H::Point, T)],
+        key: H::Point,
+        name: Option<&[u8]>,
+        f: impl Fn(&T) -> bool,
+    ) -> Result<usize, Error> {
+        if sorted_hashes.is_empty() {
+            return Err(Error::EmptyCircle);
+        }
+        let i = search_sorted(sorted_hashes, key);
+        for j in (i..sorted_hashes.len()).chain(0..i) {
+            let v = &sorted_hashes[j].1;
+            if self.is_enabled(v) && f(v) {
+                if let (Some(observer), Some(name)) = (&self.observer, name) {
+                    match core::str::from_utf8(name) {
+                        Ok(name) => observer.0.on_get(name, &v.node_key()),
+                        Err(_) => observer.0.on_get_bytes(name, &v.node_key()),
+                    }
+                }
+                return Ok(j);
+            }
+        }
+        Err(Error::EmptyCircle)
+    }
+
+    /// Like [`get`](Self::get), but also tells whether the virtual node that
+    /// owns `name` sits on a position more than one member hashed to, e.g.
+    /// to alert on collisions, which otherwise only show in
+    /// [`collision_count`](Self::collision_count). Which member holds such
+    /// a position is settled by the [`CollisionPolicy`], so lookups stay
+    /// deterministic either way.
+    ///
+    /// Positions are flagged as the members are placed by this ring, until
+    /// they leave the circle or the ring is [rebuilt](Self::rebuild); a ring
+    /// loaded with its virtual nodes, under balanced placement, flags none.
+    pub fn get_checked(&self, name: impl AsRef<str>) -> Result<(T, bool), Error> {
+        let name = name.as_ref().as_bytes();
+        let key = self.lookup_key(name);
+        let sorted_hashes = self.sorted_hashes.read();
+        let j = self.find_index_in(&sorted_hashes, key, Some(name), |_| true)?;
+        let (hash, member) = &sorted_hashes[j];
+        let contested = !self.contested.is_empty() && self.contested.contains_key(hash);
+        Ok((member.to_owned(), contested))
+    }
+
+    /// Like [`get`](Self::get), but also returns the ring position of the
+    /// virtual node that owns `name`. Disabled members are skipped as with
+    /// `get`, so this is the position of the first enabled member's virtual
+    /// node clockwise.
+    pub fn get_detailed(&self, name: impl AsRef<str>) -> Result<(T, H::Point), Error> {
+        let key = self.lookup_key(name.as_ref().as_bytes());
+        let sorted_hashes = self.sorted_hashes.read();
+        let (hash, v) = &sorted_hashes[self.find_index_in(&sorted_hashes, key, None, |_| true)?];
+        Ok((v.to_owned(), *hash))
+    }
+
+    /// Like [`get_detailed`](Self::get_detailed), but returns how far
+    /// clockwise the owning virtual node is from the hash of `name`, wrapping
+    /// around the circle. Keys with a small distance sit at the end of an arc
+    /// and are the first to move when a virtual node lands in between.
+    pub fn get_with_arc(&self, name: impl AsRef<str>) -> Result<(T, H::Point), Error> {
+        let key = self.lookup_key(name.as_ref().as_bytes());
+        let sorted_hashes = self.sorted_hashes.read();
+        let (hash, v) = &sorted_hashes[self.find_index_in(&sorted_hashes, key, None, |_| true)?];
+        Ok((v.to_owned(), hash.wrapping_sub(key)))
+    }
+
+    /// Returns the index, among the sorted virtual nodes, of the one owning
+    /// `name`, skipping disabled members as [`get`](Self::get) does. Step
+    /// clockwise from it with [`member_at`](Self::member_at); indices are
+    /// only meaningful until the ring changes.
+    pub fn position_of(&self, name: impl AsRef<str>) -> Result<usize, Error> {
+        let key = self.lookup_key(name.as_ref().as_bytes());
+        let sorted_hashes = self.sorted_hashes.read();
+        self.find_index_in(&sorted_hashes, key, None, |_| true)
+    }
+
+    /// Returns the member owning the virtual node at `index`, wrapping around
+    /// the circle, or `None` if the ring is empty.
+    pub fn member_at(&self, index: usize) -> Option<T> {
+        let sorted_hashes = self.sorted_hashes.read();
+        if sorted_hashes.is_empty() {
+            return None;
+        }
+        Some(sorted_hashes[index % sorted_hashes.len()].1.to_owned())
+    }
+
+    /// Returns the member owning `name` and, if there is one, the next distinct
+    /// member clockwise from it.
+    pub fn get_two(&self, name: impl AsRef<str>) -> Result<(T, Option<T>), Error> {
+        let key = self.lookup_key(name.as_ref().as_bytes());
+        let sorted_hashes = self.sorted_hashes.read();
+        if sorted_hashes.is_empty() {
+            return Err(Error::EmptyCircle);
+        }
+        let i = search_sorted(&sorted_hashes, key);
+        let mut walk = (i..sorted_hashes.len())
+            .chain(0..i)
+            .map(|j| &sorted_hashes[j].1)
+            .filter(|v| self.is_enabled(v));
+        let Some(a) = walk.next().map(|v| v.to_owned()) else {
+            return Err(Error::EmptyCircle);
+        };
+        if sorted_hashes.members() == 1 {
+            return Ok((a, None));
+        }
+        // The walk visits each virtual node once, so it ends after a full turn
+        // if every enabled virtual node left belongs to `a`, e.g. when the
+        // other members are disabled.
+        match walk.find(|v| !a.eq(v)) {
+            Some(b) => Ok((a, Some(b.to_owned()))),
+            None => Ok((a, None)),
+        }
+    }
+
+    /// Returns the first member counter-clockwise from the position of `name`
+    /// that differs from its owner, i.e. the mirror image of the second member
+    /// of [`get_two`](Self::get_two). With a single enabled member, that member
+    /// is returned.
+    pub fn get_prev(&self, name: impl AsRef<str>) -> Result<T, Error> {
+        let key = self.lookup_key(name.as_ref().as_bytes());
+        let sorted_hashes = self.sorted_hashes.read();
+        if sorted_hashes.is_empty() {
+            return Err(Error::EmptyCircle);
+        }
+        let i = search_sorted(&sorted_hashes, key);
+        let Some(owner) = (i..sorted_hashes.len())
+            .chain(0..i)
+            .map(|j| &sorted_hashes[j].1)
+            .find(|v| self.is_enabled(v))
+        else {
+            return Err(Error::EmptyCircle);
+        };
+        let prev = (0..i)
+            .rev()
+            .chain((i..sorted_hashes.len()).rev())
+            .map(|j| &sorted_hashes[j].1)
+            .find(|v| *v != owner && self.is_enabled(v))
+            .unwrap_or(owner);
+        Ok(prev.to_owned())
+    }
+
+    /// Returns up to `n` distinct members in ring-walk order: the owner of
+    /// `name` first, then the next distinct members clockwise.
+    ///
+    /// The walk stops once `n` members are found or every member has been
+    /// passed, and consecutive virtual nodes of the same member cost a single
+    /// comparison, so heavily weighted members are skipped cheaply. The worst
+    /// case, when fewer than `n` members qualify, is one lap of the circle:
+    /// `O(log V + V)` for `V` virtual nodes.
+    ///
+    /// With [`add_weighted`](Self::add_weighted), the first member is picked
+    /// in proportion to weight and each next one roughly in proportion to the
+    /// weights of the members not picked yet, as in drawing without
+    /// replacement. A member appears at most once per key, so heavy members
+    /// show up less often than their raw weight suggests when `n` is close to
+    /// the member count.
+    pub fn get_n(&self, name: impl AsRef<str>, n: usize) -> Result<Vec<T>, Error> {
+        self.find_n(name.as_ref().as_bytes(), n, |_| true)
+    }
+
+    /// Like [`get_n`](Self::get_n), but errors with
+    /// [`Error::InsufficientMembers`] instead of returning fewer than `n`
+    /// members.
+    pub fn get_n_strict(&self, name: impl AsRef<str>, n: usize) -> Result<Vec<T>, Error> {
+        let res = self.get_n(name, n)?;
+        if res.len() < n {
+            return Err(Error::InsufficientMembers {
+                requested: n,
+                available: res.len(),
+            });
+        }
+        Ok(res)
+    }
+
+    /// Like [`get_n`](Self::get_n), but splits the result into the member
+    /// owning `name`, as returned by [`get`](Self::get), and its clockwise
+    /// replicas. `n` counts the primary, so up to `n - 1` replicas follow it.
+    pub fn get_replicas(&self, name: impl AsRef<str>, n: usize) -> Result<Replicas<T>, Error> {
+        let mut members = self.get_n(name, n.max(1))?.into_iter();
+        let primary = members.next().ok_or(Error::EmptyCircle)?;
+        Ok(Replicas {
+            primary,
+            replicas: members.collect(),
+        })
+    }
+
+    /// Like [`get_n`](Self::get_n), but skips the members whose key is in
+    /// `exclude`. Fewer than `n` members are returned if not enough qualify.
+    pub fn get_n_excluding<S: BuildHasher>(
+        &self,
+        name: impl AsRef<str>,
+        n: usize,
+        exclude: &HashSet<FastStr, S>,
+    ) -> Result<Vec<T>, Error> {
+        self.find_n(name.as_ref().as_bytes(), n, |v| {
+            exclude.is_empty() || !exclude.contains(&v.node_key())
+        })
+    }
+
+    /// Returns up to `n` distinct enabled members clockwise from `name` that
+    /// satisfy `f`.
+    fn find_n(&self, name: &[u8], mut n: usize, f: impl Fn(&T) -> bool) -> Result<Vec<T>, Error> {
+        let key = self.lookup_key(name);
+        let sorted_hashes = self.sorted_hashes.read();
+        if sorted_hashes.is_empty() {
+            return Err(Error::EmptyCircle);
+        }
+        let count = sorted_hashes.members().saturating_sub(self.disabled.len());
+        if count == 0 {
+            return Err(Error::EmptyCircle);
+        }
+        if count < n {
+            n = count;
+        }
+        Ok(collect_n(
+            &sorted_hashes,
+            key,
+            sorted_hashes.members(),
+            n,
+            |v| self.is_enabled(v) && f(v),
+        ))
+    }
+
+    /// Like [`get_n`](Self::get_n), but spreads the members over distinct
+    /// tags, e.g. racks: walking clockwise from `name`, a member is skipped if
+    /// its tag was already picked. Untagged members never clash. If that
+    /// leaves fewer than `n`, the skipped members fill the rest, in the order
+    /// they were walked past.
+    pub fn get_n_distinct_tags(&self, name: impl AsRef<str>, n: usize) -> Result<Vec<T>, Error> {
+        let key = self.lookup_key(name.as_ref().as_bytes());
+        let sorted_hashes = self.sorted_hashes.read();
+        if sorted_hashes.is_empty() {
+            return Err(Error::EmptyCircle);
+        }
+        let members = sorted_hashes.members();
+        let n = n.min(members.saturating_sub(self.disabled.len()));
+        if n == 0 {
+            return Err(Error::EmptyCircle);
+        }
+        let i = search_sorted(&sorted_hashes, key);
+        let mut res = Vec::with_capacity(n);
+        let mut skipped = Vec::new();
+        let mut seen = FxHashSet::default();
+        let mut tags = FxHashSet::default();
+        for j in (i..sorted_hashes.len()).chain(0..i) {
+            if res.len() == n || seen.len() == members {
+                break;
+            }
+            let v = &sorted_hashes[j].1;
+            let elt = v.node_key();
+            if !seen.insert(elt.clone()) || !self.is_enabled(v) {
+                continue;
+            }
+            match self.tags.get(&elt) {
+                Some(tag) if !tags.insert(tag.value().to_owned()) => skipped.push(v.to_owned()),
+                _ => res.push(v.to_owned()),
+            }
+        }
+        let missing = n - res.len();
+        res.extend(skipped.into_iter().take(missing));
+        Ok(res)
+    }
+
+    /// Like [`get_n`](Self::get_n), but spans at least `min_zones` distinct
+    /// zones, taking each member's tag from [`add_tagged`](Self::add_tagged)
+    /// as its zone. Walking clockwise from `name`, members of zones already
+    /// picked, and untagged ones, are passed over while the remaining slots
+    /// are needed for new zones, then fill any slots left at the end.
+    ///
+    /// Also returns whether the members span `min_zones` zones. If there are
+    /// not enough zones, or `n` is too small, the result is best-effort.
+    pub fn get_n_across_zones(
+        &self,
+        name: impl AsRef<str>,
+        n: usize,
+        min_zones: usize,
+    ) -> Result<(Vec<T>, bool), Error> {
+        let key = self.lookup_key(name.as_ref().as_bytes());
+        let sorted_hashes = self.sorted_hashes.read();
+        if sorted_hashes.is_empty() {
+            return Err(Error::EmptyCircle);
+        }
+        let members = sorted_hashes.members();
+        let n = n.min(members.saturating_sub(self.disabled.len()));
+        if n == 0 {
+            return Err(Error::EmptyCircle);
+        }
+        let i = search_sorted(&sorted_hashes, key);
+        let mut res = Vec::with_capacity(n);
+        let mut skipped = Vec::new();
+        let mut seen = FxHashSet::default();
+        let mut zones = FxHashSet::default();
+        for j in (i..sorted_hashes.len()).chain(0..i) {
+            if res.len() == n || seen.len() == members {
+                break;
+            }
+            let v = &sorted_hashes[j].1;
+            let elt = v.node_key();
+            if !seen.insert(elt.clone()) || !self.is_enabled(v) {
+                continue;
+            }
+            let zone = self.tags.get(&elt).map(|zone| zone.value().to_owned());
+            // Members of new zones are always taken, others only while enough
+            // slots are left for the zones still missing.
+            if zone.is_some_and(|zone| zones.insert(zone))
+                || res.len() + min_zones.saturating_sub(zones.len()) < n
+            {
+                res.push(v.to_owned());
+            } else {
+                skipped.push(v.to_owned());
+            }
+        }
+        let missing = n - res.len();
+        res.extend(skipped.into_iter().take(missing));
+        Ok((res, zones.len() >= min_zones))
+    }
+
+    /// Returns every member in ring order, walking clockwise from the first
+    /// virtual node of `start`, so `start` comes first. Disabled members are
+    /// listed too. A member whose virtual nodes were all lost to collisions
+    /// is followed by the members from where its first one would have gone.
+    /// Errors with [`Error::MemberNotFound`] if `start` is not a member.
+    pub fn members_from(&self, start: impl AsRef<str>) -> Result<Vec<T>, Error> {
+        let start = start.as_ref();
+        let sorted_hashes = self.sorted_hashes.read();
+        let elt = self
+            .members
+            .get(start)
+            .map(|m| m.value().to_owned())
+            .ok_or_else(|| Error::MemberNotFound(FastStr::new(start)))?;
+        let i = sorted_hashes
+            .iter()
+            .position(|(_, v)| *v == elt)
+            .unwrap_or_else(|| {
+                let key = self.replica_key_format.probe_key(start, 0, 0);
+                search_sorted(&sorted_hashes, self.hash_key(key.as_bytes()))
+            });
+        let members = sorted_hashes.members();
+        let mut res = Vec::with_capacity(members);
+        let mut seen = FxHashSet::default();
+        seen.insert(elt.clone());
+        res.push(elt);
+        let mut last = None;
+        for j in (i..sorted_hashes.len()).chain(0..i) {
+            if seen.len() == members {
+                break;
+            }
+            let v = &sorted_hashes[j].1;
+            if last == Some(v) {
+                continue;
+            }
+            last = Some(v);
+            if seen.insert(v.to_owned()) {
+                res.push(v.to_owned());
+            }
+        }
+        Ok(res)
+    }
+
+    /// Returns the distinct members owning any key whose hash lies in the
+    /// clockwise span from the hash of `start` to the hash of `end`, both
+    /// included, in ring-walk order. The span wraps around the top of the
+    /// circle when `start` hashes above `end`.
+    ///
+    /// Disabled members are left out; the keys they would own go to the next
+    /// enabled member clockwise, which is included even past the span.
+    pub fn members_in_range(
+        &self,
+        start: impl AsRef<str>,
+        end: impl AsRef<str>,
+    ) -> Result<Vec<T>, Error> {
+        let start = self.lookup_key(start.as_ref().as_bytes());
+        let end = self.lookup_key(end.as_ref().as_bytes());
+        let sorted_hashes = self.sorted_hashes.read();
+        if sorted_hashes.is_empty() {
+            return Err(Error::EmptyCircle);
+        }
+        let len = sorted_hashes.len();
+        let i = search_sorted(&sorted_hashes, start);
+        let j = search_sorted(&sorted_hashes, end);
+        // Owners of the span run from the owner of `start` to the owner of
+        // `end`, or all the way around if both fall in one arc but the span
+        // passes its virtual node, going around the circle to get back.
+        let span = end.wrapping_sub(start);
+        let steps = match (j + len - i) % len {
+            0 if sorted_hashes[i].0.wrapping_sub(start) <= span => len,
+            n => n + 1,
+        };
+        let mut res = Vec::new();
+        let mut seen = FxHashSet::default();
+        let mut walk = (i..len).chain(0..i).map(|k| &sorted_hashes[k].1);
+        let mut last_enabled = false;
+        for v in walk.by_ref().take(steps) {
+            last_enabled = self.is_enabled(v);
+            if last_enabled && seen.insert(v.to_owned()) {
+                res.push(v.to_owned());
+            }
+        }
+        if !last_enabled {
+            if let Some(v) = walk.find(|v| self.is_enabled(v)) {
+                if seen.insert(v.to_owned()) {
+                    res.push(v.to_owned());
+                }
+            }
+        }
+        if res.is_empty() {
+            return Err(Error::EmptyCircle);
+        }
+        Ok(res)
+    }
+
+    /// Stops routing to `elt` while keeping its virtual nodes on the circle;
+    /// lookups skip it and continue clockwise. Unknown members are ignored.
+    pub fn disable(&self, elt: impl AsRef<str>) {
+        if let Some(member) = self.members.get(elt.as_ref()) {
+            self.disabled.insert(member.key().to_owned());
+        }
+    }
+
+    /// Resumes routing to a member stopped by [`disable`](Self::disable). This
+    /// does not touch the circle, so it takes effect immediately.
+    pub fn enable(&self, elt: impl AsRef<str>) {
+        self.disabled.remove(elt.as_ref());
+    }
+
+    fn is_enabled(&self, elt: &T) -> bool {
+        self.disabled.is_empty() || !self.disabled.contains(&elt.node_key())
+    }
+
+    /// Puts `elt` on the circle with `replicas` virtual nodes, replacing any
+    /// previous placement. Returns the hashes dropped from and then added to
+    /// the circle, in that order; the caller is responsible for bringing
+    /// `sorted_hashes` up to date.
+    fn place(&self, elt: T, replicas: usize) -> (Vec<H::Point>, Vec<H::Point>) {
+        let weight = replicas as f64 / self.number_of_replicas() as f64;
+        self.place_weighted(elt, replicas, weight)
+    }
+
+    /// Like [`place`](Self::place), but records `weight` as the member's
+    /// weight rather than deriving it from `replicas`.
+    fn place_weighted(
+        &self,
+        elt: T,
+        replicas: usize,
+        weight: f64,
+    ) -> (Vec<H::Point>, Vec<H::Point>) {
+        let key = elt.node_key();
+        let removed = self.detach(&key).unwrap_or_default();
+        let added = self.place_replicas(&elt, &key, 0..replicas);
+        self.attach(key, elt, replicas, weight);
+        (removed, added)
+    }
+
+    /// Returns the replica count of a member with `weight`: the replica count
+    /// times `weight`, rounded and at least one.
+    fn replicas_for(&self, weight: f64) -> usize {
+        ((self.number_of_replicas() as f64 * weight).round() as usize).max(1)
+    }
+
+    /// Records `elt` as a member once its virtual nodes are on the circle.
+    fn attach(&self, key: FastStr, elt: T, replicas: usize, weight: f64) {
+        self.replicas.insert(key.clone(), replicas);
+        self.weights.insert(key.clone(), weight);
+        self.members.insert(key.clone(), elt);
+        self.sorted_hashes.set_members(self.members.len());
+        if let Some(observer) = &self.observer {
+            observer.0.on_add(&key);
+        }
+    }
+
+    /// Takes `elt` off the circle, returning the hashes that were dropped.
+    ///
+    /// A virtual node may have been probed away from its first slot, so the
+    /// probe sequences of all replicas are walked in lockstep until every slot
+    /// owned by `elt` has been found. Only slots `elt` owns are removed.
+    fn unplace(&self, elt: &str) -> Vec<H::Point> {
+        let Some(removed) = self.detach(elt) else {
+            return Vec::new();
+        };
+        if let Some(observer) = &self.observer {
+            observer.0.on_remove(elt);
+        }
+        removed
+    }
+
+    /// Like [`unplace`](Self::unplace), but returns `None` if `elt` is not a
+    /// member and does not notify the observer, for re-placing a member.
+    fn detach(&self, elt: &str) -> Option<Vec<H::Point>> {
+        let (_, node) = self.members.remove(elt)?;
+        self.sorted_hashes.set_members(self.members.len());
+        self.disabled.remove(elt);
+        self.tags.remove(elt);
+        self.weights.remove(elt);
+        let replicas = self
+            .replicas
+            .remove(elt)
+            .map_or(0, |(_, replicas)| replicas);
+        Some(self.unplace_replicas(&node, elt, 0..replicas))
+    }
+
+    /// Puts the virtual nodes of `elt` with the replica indices in `range` on
+    /// the circle, resolving collisions by the collision policy. Returns the
+    /// hashes added.
+    fn place_replicas(&self, elt: &T, key: &str, range: Range<usize>) -> Vec<H::Point> {
+        // Spacing is checked against the member's virtual nodes already on the
+        // circle as well as those placed here.
+        let siblings = if self.min_replica_spacing != H::Point::ZERO && range.start > 0 {
+            self.circle
+                .iter()
+                .filter(|e| e.value() == elt)
+                .map(|e| *e.key())
+                .collect()
+        } else {
+            Vec::with_capacity(range.len())
+        };
+        let existing = siblings.len();
+        let mut placed = self.plan_replicas(
+            key,
+            range,
+            siblings,
+            |hash, _| self.occupant(hash),
+            |step| self.apply_step(elt, key, step),
+        );
+        placed.split_off(existing)
+    }
+
+    /// Works out, replica by replica, where the virtual nodes of `key` with
+    /// the replica indices in `range` land by the placement rules: balanced
+    /// placement, the collision policy and the minimum replica spacing.
+    /// `occupant` tells which member holds a position, given `siblings`, the
+    /// member's virtual nodes so far. Each step is passed to `step` before the
+    /// next replica is worked out, so it may put the virtual node on the
+    /// circle. Returns `siblings` with the landed positions appended.
+    fn plan_replicas(
+        &self,
+        key: &str,
+        range: Range<usize>,
+        mut siblings: Vec<H::Point>,
+        occupant: impl Fn(H::Point, &[H::Point]) -> Option<FastStr>,
+        mut step: impl FnMut(Step<H::Point>),
+    ) -> Vec<H::Point> {
+        let mut points = self.balances().then(|| {
+            let mut points = self.circle.iter().map(|e| *e.key()).collect::<Vec<_>>();
+            points.sort_unstable();
+            points
+        });
+        for i in range {
+            let balanced = points
+                .as_ref()
+                .and_then(|points| self.balanced_slot(key, i, points, &siblings));
+            let hash = match balanced {
+                Some((hash, relocated)) => {
+                    step(Step::Landed { hash, relocated });
+                    hash
+                }
+                None => match self.probe_slot(key, i, None, &siblings, &occupant, &mut step) {
+                    Some(hash) => hash,
+                    None => continue,
+                },
+            };
+            if let Some(points) = &mut points {
+                points.insert(points.partition_point(|p| *p < hash), hash);
+            }
+            siblings.push(hash);
+        }
+        siblings
+    }
+
+    /// Returns the key of the member holding `hash` on the circle.
+    fn occupant(&self, hash: H::Point) -> Option<FastStr> {
+        self.circle.get(&hash).map(|v| v.node_key())
+    }
+
+    /// Applies a placement step of `elt` to the circle and the counters.
+    fn apply_step(&self, elt: &T, key: &str, step: Step<H::Point>) {
+        match step {
+            Step::Collided { hash, occupant } => {
+                self.collisions.fetch_add(1, Ordering::Relaxed);
+                if occupant != key {
+                    *self.contested.entry(hash).or_default() += 1;
+                }
+            }
+            Step::Landed { hash, relocated } => {
+                if relocated {
+                    self.relocations.fetch_add(1, Ordering::Relaxed);
+                }
+                self.circle.insert(hash, elt.clone());
+            }
+        }
+    }
+
+    fn balances(&self) -> bool {
+        self.balanced_placement && self.collision_policy == CollisionPolicy::Probe
+    }
+
+    /// Whether `hash` is at least the minimum replica spacing away from each
+    /// of `siblings`, both ways around the circle.
+    fn spaced(&self, hash: H::Point, siblings: &[H::Point]) -> bool {
+        siblings.iter().all(|&sibling| {
+            hash.wrapping_sub(sibling).min(sibling.wrapping_sub(hash)) >= self.min_replica_spacing
+        })
+    }
+
+    /// Picks, among the free first [`BALANCE_CANDIDATES`] probe positions of
+    /// replica `i` that are spaced from its `siblings`, the one farthest from
+    /// its neighbours in the sorted `points`, or `None` if there is none. Also
+    /// tells whether the spacing ruled out the position that would otherwise
+    /// have been picked.
+    fn balanced_slot(
+        &self,
+        key: &str,
+        i: usize,
+        points: &[H::Point],
+        siblings: &[H::Point],
+    ) -> Option<(H::Point, bool)> {
+        let mut best = None;
+        let mut unspaced_best = None;
+        for probe in 0..BALANCE_CANDIDATES {
+            let hash = self.hash_key(self.replica_key_format.probe_key(key, i, probe).as_bytes());
+            let j = points.partition_point(|p| *p < hash);
+            if points.get(j) == Some(&hash) {
+                continue;
+            }
+            let split = match points.len() {
+                0 => H::Point::MAX,
+                len => {
+                    let prev = points[j.checked_sub(1).unwrap_or(len - 1)];
+                    let next = points[j % len];
+                    hash.wrapping_sub(prev).min(next.wrapping_sub(hash))
+                }
+            };
+            if unspaced_best.is_none_or(|(_, best)| split > best) {
+                unspaced_best = Some((hash, split));
+            }
+            if self.spaced(hash, siblings) && best.is_none_or(|(_, best)| split > best) {
+                best = Some((hash, split));
+            }
+        }
+        best.map(|(hash, _)| (hash, best != unspaced_best))
+    }
+
+    /// Puts replica `i` of `elt` on the circle, returning its hash unless every
+    /// probe collided. `first` is the precomputed hash of the first probe.
+    #[cfg(feature = "rayon")]
+    fn place_replica(
+        &self,
+        elt: &T,
+        key: &str,
+        i: usize,
+        first: Option<H::Point>,
+        siblings: &[H::Point],
+    ) -> Option<H::Point> {
+        self.probe_slot(
+            key,
+            i,
+            first,
+            siblings,
+            &|hash, _| self.occupant(hash),
+            &mut |step| self.apply_step(elt, key, step),
+        )
+    }
+
+    /// Probes for the position of replica `i` of `key` by the collision
+    /// policy, passing each collision and the landing to `step`, and returns
+    /// it unless every probe collided. `first` is the precomputed hash of the
+    /// first probe. Free positions too close to `siblings` are passed over
+    /// unless there is no other.
+    fn probe_slot(
+        &self,
+        key: &str,
+        i: usize,
+        first: Option<H::Point>,
+        siblings: &[H::Point],
+        occupant: &impl Fn(H::Point, &[H::Point]) -> Option<FastStr>,
+        step: &mut impl FnMut(Step<H::Point>),
+    ) -> Option<H::Point> {
+        let mut unspaced = None;
+        for probe in 0..=self.collision_policy.max_probes() {
+            let hash = match first {
+                Some(hash) if probe == 0 => hash,
+                _ => self.hash_key(self.replica_key_format.probe_key(key, i, probe).as_bytes()),
+            };
+            match occupant(hash, siblings) {
+                None => {
+                    if !self.spaced(hash, siblings) {
+                        unspaced.get_or_insert(hash);
+                        continue;
+                    }
+                    let relocated = unspaced.is_some();
+                    step(Step::Landed { hash, relocated });
+                    return Some(hash);
+                }
+                Some(occupant) => {
+                    let displaces = self.collision_policy.displaces(key, &occupant);
+                    step(Step::Collided { hash, occupant });
+                    if displaces {
+                        step(Step::Landed {
+                            hash,
+                            relocated: false,
+                        });
+                        return Some(hash);
+                    }
+                }
+            }
+        }
+        let hash = unspaced?;
+        step(Step::Landed {
+            hash,
+            relocated: false,
+        });
+        Some(hash)
+    }
+
+    /// Takes the virtual nodes of `elt` with the replica indices in `range` off
+    /// the circle, returning the hashes that were dropped. The positions each
+    /// replica probed on its way to its own are no longer contested by it, so
+    /// they lose one contender, and are unflagged once they have none left.
+    fn unplace_replicas(&self, elt: &T, key: &str, range: Range<usize>) -> Vec<H::Point> {
+        let mut removed = Vec::with_capacity(range.len());
+        let mut pending = range.collect::<Vec<_>>();
+        for probe in 0..=self.collision_policy.max_probes() {
+            if pending.is_empty() {
+                break;
+            }
+            pending.retain(|&i| {
+                let hash =
+                    self.hash_key(self.replica_key_format.probe_key(key, i, probe).as_bytes());
+                let found = self.circle.remove_if(&hash, |_, v| v == elt).is_some();
+                if found {
+                    removed.push(hash);
+                }
+                if !self.contested.is_empty() {
+                    if found {
+                        self.contested.remove(&hash);
+                    } else {
+                        self.contested.remove_if_mut(&hash, |_, contenders| {
+                            *contenders -= 1;
+                            *contenders == 0
+                        });
+                    }
+                }
+                !found
+            });
+        }
+        removed
+    }
+
+    /// Walks the ring clockwise from the position of `name`, yielding each
+    /// distinct member once.
+    ///
+    /// The read lock is taken per step rather than held by the iterator, so the
+    /// ring may be mutated while iterating; the walk then continues over the
+    /// updated ring.
+    pub fn get_iter(&self, name: impl AsRef<str>) -> impl Iterator<Item = T> + '_ {
+        let mut j = self.search(self.lookup_key(name.as_ref().as_bytes()));
+        let mut steps = 0;
+        let mut seen = FxHashSet::default();
+        std::iter::from_fn(move || {
+            let sorted_hashes = self.sorted_hashes.read();
+            while steps < sorted_hashes.len() && seen.len() < sorted_hashes.members() {
+                let i = j % sorted_hashes.len();
+                j = i + 1;
+                steps += 1;
+                let v = &sorted_hashes[i].1;
+                if self.is_enabled(v) && seen.insert(v.to_owned()) {
+                    return Some(v.to_owned());
+                }
+            }
+            None
+        })
+    }
+
+    /// Checks the ring's internal invariants, describing the first one that
+    /// does not hold: `sorted_hashes` is strictly ascending and mirrors `circle`
+    /// exactly, every virtual node belongs to a member, and the member count
+    /// matches the members.
+    #[cfg(any(test, feature = "validate"))]
+    pub fn validate(&self) -> Result<(), String> {
+        let sorted_hashes = self.sorted_hashes.lock();
+        if let Some(w) = sorted_hashes.windows(2).find(|w| w[0].0 >= w[1].0) {
+            return Err(format!(
+                "sorted_hashes out of order at {:?} -> {:?}",
+                w[0].0, w[1].0
+            ));
+        }
+        if sorted_hashes.len() != self.circle.len() {
+            return Err(format!(
+                "sorted_hashes has {} entries but circle has {}",
+                sorted_hashes.len(),
+                self.circle.len()
+            ));
+        }
+        for (hash, member) in sorted_hashes.iter() {
+            match self.circle.get(hash) {
+                None => return Err(format!("hash {hash:?} is missing from circle")),
+                Some(v) if v.node_key() != member.node_key() => {
+                    return Err(format!(
+                        "hash {hash:?} maps to {} in circle but {} in sorted_hashes",
+                        v.node_key(),
+                        member.node_key()
+                    ))
+                }
+                Some(_) => {}
+            }
+            if !self.members.contains_key(&member.node_key()) {
+                return Err(format!(
+                    "hash {hash:?} belongs to {}, which is not a member",
+                    member.node_key()
+                ));
+            }
+        }
+        let count = sorted_hashes.members();
+        if count != self.members.len() {
+            return Err(format!(
+                "count is {count} but there are {} members",
+                self.members.len()
+            ));
+        }
+        Ok(())
+    }
+
+    fn search(&self, key: H::Point) -> usize {
+        search_sorted(&self.sorted_hashes.read(), key)
+    }
+
+    fn hash_key(&self, key: &[u8]) -> H::Point {
+        hash_key(&self.hasher, self.hash_seed, key)
+    }
+
+    /// Hashes the lookup key `name`, after folding its case and prepending the
+    /// key namespace as configured.
+    fn lookup_key(&self, name: &[u8]) -> H::Point {
+        let name = lookup_bytes(name, self.case_insensitive, self.key_namespace.as_deref());
+        self.hash_key(&name)
+    }
+
+    fn rebuild_sorted_hashes(&self, sorted_hashes: &mut Vec<(H::Point, T)>) {
+        sorted_hashes.clear();
+        if self.bulk_depth.load(Ordering::Relaxed) == 0 {
+            self.shrink_sorted_hashes(sorted_hashes);
+        }
+        for k in self.circle.iter() {
+            sorted_hashes.push((*k.key(), k.value().to_owned()));
+        }
+        sorted_hashes.sort_unstable_by_key(|(hash, _)| *hash);
+    }
+
+    /// Gives back the spare capacity of `sorted_hashes` once it is far larger
+    /// than the circle.
+    fn shrink_sorted_hashes(&self, sorted_hashes: &mut Vec<(H::Point, T)>) {
+        if sorted_hashes.capacity() / (self.number_of_replicas() * 4) > self.circle.len() {
+            sorted_hashes.shrink_to(self.circle.len());
+        }
+    }
+}
+
+/// How many times a colliding virtual node is re-hashed before its slot is
+/// given up.
+const MAX_PROBES: usize = 8;
+
+/// What placing a virtual node ran into, as worked out by
+/// [`Consistent::plan_replicas`].
+#[cfg(feature = "std")]
+enum Step<P> {
+    /// The position `hash` was held, by the member keyed `occupant`.
+    Collided { hash: P, occupant: FastStr },
+    /// The virtual node took `hash`, away from its best position if
+    /// `relocated`, to keep its spacing.
+    Landed { hash: P, relocated: bool },
+}
+
+/// The largest replica count, the ring's or a member's, that a loaded ring may
+/// have, so that a corrupted count is rejected rather than placed.
+#[cfg(feature = "std")]
+const MAX_REPLICAS: usize = 1 << 24;
+
+/// How many probe positions balanced placement chooses from. They are the
+/// first of the probe sequence, so removal finds them like probed ones.
+#[cfg(feature = "std")]
+const BALANCE_CANDIDATES: usize = 4;
+
+/// What happens when a virtual node hashes to a slot already taken by another
+/// one.
+#[cfg(feature = "std")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum CollisionPolicy {
+    /// Re-hash the virtual-node key with an increasing salt, `{key}#{probe}`,
+    /// until a free slot is found, so every member keeps its full replica
+    /// count. A slot is only lost if all of its probes collide. Removal walks
+    /// the same probe sequence to find the slots.
+    #[default]
+    Probe,
+    /// Hand the slot to the newer virtual node, as in `stathat/consistent`.
+    /// The older member loses it, even once the newer one is removed.
+    Overwrite,
+    /// Hand the slot to the member with the lexicographically smallest key,
+    /// so the winner does not depend on the order members were added in and
+    /// rebuilt rings agree. The loser gives the slot up, even once the winner
+    /// is removed.
+    KeepLowest,
+}
+
+#[cfg(feature = "std")]
+impl CollisionPolicy {
+    fn max_probes(self) -> usize {
+        match self {
+            CollisionPolicy::Probe => MAX_PROBES,
+            CollisionPolicy::Overwrite | CollisionPolicy::KeepLowest => 0,
+        }
+    }
+
+    /// Whether a virtual node of `elt` takes a slot held by `occupant`.
+    fn displaces(self, elt: &str, occupant: &str) -> bool {
+        match self {
+            CollisionPolicy::Probe => false,
+            CollisionPolicy::Overwrite => true,
+            CollisionPolicy::KeepLowest => elt < occupant,
+        }
+    }
+}
+
+/// How the key of a member's virtual node is built from the member and the
+/// replica index, to match the placement of other implementations.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum ReplicaKeyFormat {
+    /// `{idx}{elt}`, as in `stathat/consistent`.
+    #[default]
+    IndexPrefix,
+    /// `{elt}{idx}`.
+    MemberPrefix,
+    /// `{elt}-{idx}`, as in Ketama.
+    Ketama,
+}
+
+impl ReplicaKeyFormat {
+    fn elt_key(self, elt: &str, idx: usize) -> String {
+        match self {
+            ReplicaKeyFormat::IndexPrefix => format!("{}{}", idx, elt),
+            ReplicaKeyFormat::MemberPrefix => format!("{}{}", elt, idx),
+            ReplicaKeyFormat::Ketama => format!("{}-{}", elt, idx),
+        }
+    }
+
+    fn probe_key(self, elt: &str, idx: usize, probe: usize) -> String {
+        if probe == 0 {
+            self.elt_key(elt, idx)
+        } else {
+            format!("{}#{}", self.elt_key(elt, idx), probe)
+        }
+    }
+}
+
+/// Returns the bytes a lookup key is hashed as: with `case_insensitive`, `key`
+/// lowercased as a string or, if it is not valid UTF-8, as ASCII, so that it
+/// folds the same whether it is looked up as `&str` or as bytes; then
+/// prefixed with `namespace`, if any.
+#[cfg(feature = "std")]
+fn lookup_bytes<'a>(
+    key: &'a [u8],
+    case_insensitive: bool,
+    namespace: Option<&str>,
+) -> Cow<'a, [u8]> {
+    let mut key = Cow::Borrowed(key);
+    if case_insensitive {
+        key = match core::str::from_utf8(&key) {
+            Ok(key) => Cow::Owned(key.to_lowercase().into_bytes()),
+            Err(_) => Cow::Owned(key.to_ascii_lowercase()),
+        };
+    }
+    if let Some(namespace) = namespace {
+        key = Cow::Owned([namespace.as_bytes(), &key].concat());
+    }
+    key
+}
+
+/// Parses a weight spec for [`Consistent::add_weighted_str`] into a
+/// multiplier.
+#[cfg(feature = "std")]
+fn parse_weight(spec: &str) -> Result<f64, Error> {
+    let trimmed = spec.trim();
+    let (number, scale) = if let Some(percent) = trimmed.strip_suffix('%') {
+        (percent, 100.0)
+    } else if let Some(times) = trimmed.strip_suffix(['x', 'X']) {
+        (times, 1.0)
+    } else {
+        (trimmed, 1.0)
+    };
+    match number.trim_end().parse::<f64>() {
+        Ok(weight) if weight.is_finite() && weight > 0.0 => Ok(weight / scale),
+        _ => Err(Error::InvalidWeight(String::from(spec))),
+    }
+}
+
+fn hash_key<H: RingHasher>(hasher: &H, hash_seed: u64, key: &[u8]) -> H::Point {
+    if hash_seed == 0 {
+        hasher.hash(key)
+    } else {
+        let mut salted = format!("{}:", hash_seed).into_bytes();
+        salted.extend_from_slice(key);
+        hasher.hash(&salted)
+    }
+}
+
+/// Walks `sorted_hashes` clockwise from `key`, collecting up to `n` distinct
+/// members that satisfy `f`. The walk stops early once all `members`
+/// distinct members have been seen.
+#[cfg(feature = "std")]
+fn collect_n<P: Point, T: Node>(
+    sorted_hashes: &[(P, T)],
+    key: P,
+    members: usize,
+    n: usize,
+    f: impl Fn(&T) -> bool,
+) -> Vec<T> {
+    let i = search_sorted(sorted_hashes, key);
+    let mut res = Vec::with_capacity(n);
+    let mut seen = FxHashSet::default();
+    let mut last = None;
+    for (_, v) in sorted_hashes[i..].iter().chain(&sorted_hashes[..i]) {
+        if res.len() == n || seen.len() == members {
+            break;
+        }
+        // Runs of one member's virtual nodes are common on weighted rings.
+        if last == Some(v) {
+            continue;
+        }
+        last = Some(v);
+        if !seen.insert(v) {
+            continue;
+        }
+        if f(v) {
+            res.push(v.to_owned());
+        }
+    }
+    res
+}
+
+#[cfg(feature = "std")]
+fn search_sorted<P: Point, T>(sorted_hashes: &[(P, T)], key: P) -> usize {
+    let i = sorted_hashes.partition_point(|(x, _)| *x <= key);
+    if i >= sorted_hashes.len() {
+        0
+    } else {
+        i
+    }
+}
+
+#[cfg(feature = "std")]
+/// Inserts the virtual node at `hash`, or hands it to `elt` if it already
+/// exists.
+fn insert_sorted<P: Point, T>(sorted_hashes: &mut Vec<(P, T)>, hash: P, elt: T) {
+    let i = sorted_hashes.partition_point(|(x, _)| *x < hash);
+    match sorted_hashes.get_mut(i) {
+        Some((x, v)) if *x == hash => *v = elt,
+        _ => sorted_hashes.insert(i, (hash, elt)),
+    }
+}
+
+#[cfg(feature = "std")]
+fn remove_sorted<P: Point, T>(sorted_hashes: &mut Vec<(P, T)>, hash: P) {
+    if let Ok(i) = sorted_hashes.binary_search_by_key(&hash, |(x, _)| *x) {
+        sorted_hashes.remove(i);
+    }
+}
+
+/// The members holding a key, as returned by
+/// [`Consistent::get_replicas`].
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replicas<T = FastStr> {
+    /// The member owning the key's position on the ring.
+    pub primary: T,
+    /// The next distinct members clockwise from the primary, in ring-walk
+    /// order.
+    pub replicas: Vec<T>,
+}
+
+/// The state of a ring after [`Consistent::rebuild`].
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RebuildReport {
+    /// The number of virtual nodes on the circle.
+    pub virtual_nodes: usize,
+    /// How many virtual nodes landed on an occupied slot while placing them.
+    pub collisions: usize,
+    /// How many virtual nodes were moved away from their siblings while
+    /// placing them.
+    pub relocations: usize,
+    /// The number of members.
+    pub members: usize,
+}
+
+/// The member changes between two rings, as returned by
+/// [`Consistent::diff`].
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RingDiff<T = FastStr> {
+    /// The members only in the other ring.
+    pub added: Vec<T>,
+    /// The members only in this ring.
+    pub removed: Vec<T>,
+}
+
+/// The errors returned by the rings.
+///
+/// More variants may be added in minor releases, so matches need a catch-all
+/// arm:
+///
+/// ```
+/// use ringhash::{Consistent, Error};
+///
+/// let c = Consistent::new();
+/// match c.get("user_mcnulty") {
+///     Ok(member) => println!("routed to {member}"),
+///     Err(Error::EmptyCircle) => println!("no members yet"),
+///     Err(e) => println!("lookup failed: {e}"),
+/// }
+/// ```
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("empty circle")]
+    EmptyCircle,
+    #[error("member not found: {0}")]
+    MemberNotFound(FastStr),
+    #[error("invalid utf-8 in member key: {0}")]
+    InvalidUtf8(#[from] Utf8Error),
+    #[error("insufficient members: requested {requested}, available {available}")]
+    InsufficientMembers { requested: usize, available: usize },
+    #[error("malformed ring state: {0}")]
+    Deserialize(&'static str),
+    #[error("every member is at capacity")]
+    AtCapacity,
+    #[error("invalid weight: {0:?}")]
+    InvalidWeight(String),
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::{
+        collections::{HashMap, HashSet},
+        net::SocketAddr,
+        sync::{Arc, Mutex, OnceLock, Weak},
+    };
+
+    use faststr::FastStr;
+
+    use crate::{
+        CollisionPolicy, Consistent, Consistent64, Error, FxHasher32, FxHasher64, Hasher32,
+        LoadTracker, Node, RebuildReport, ReplicaKeyFormat, RingDiff, RingHasher, RingObserver,
+    };
+
+    #[test]
+    fn test_add() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        assert_eq!(c.circle.len(), 20);
+        assert_eq!(c.sorted_hashes.read().len(), 20);
+        c.add("qwer");
+        assert_eq!(c.circle.len(), 40);
+        assert_eq!(c.sorted_hashes.read().len(), 40);
+    }
+
+    #[test]
+    fn test_remove() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        c.remove("abcdefg");
+        assert_eq!(c.circle.len(), 0);
+        assert_eq!(c.sorted_hashes.read().len(), 0);
+    }
+
+    #[test]
+    fn test_remove_non_existing() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        c.remove("abcdefghijk");
+        assert_eq!(c.circle.len(), 20);
+    }
+
+    #[test]
+    fn test_get_empty() {
+        let c = Consistent::new();
+        let res = c.get("asdfsadfsadf");
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_get_single() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        let res = c.get("asdfsadfsadf");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), "abcdefg");
+    }
+
+    #[test]
+    fn test_get_two() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        c.add("opqrstu");
+        let res = c.get_two("asdfsadfsadf");
+        assert!(res.is_ok());
+        let (a, b) = res.unwrap();
+        assert!(b.is_some());
+        assert_ne!(a, b.unwrap());
+    }
+
+    #[test]
+    fn test_get_two_single() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        let res = c.get_two("asdfsadfsadf");
+        assert_eq!(res.unwrap(), ("abcdefg".into(), None));
+    }
+
+    #[test]
+    fn test_get_two_no_distinct() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        c.add("opqrstu");
+        c.disable("opqrstu");
+        assert_eq!(c.get_two("asdfsadfsadf").unwrap(), ("abcdefg".into(), None));
+
+        let c = Consistent::new();
+        c.add("abcdefg");
+        c.add("opqrstu");
+        for (_, member) in c.sorted_hashes.write().iter_mut() {
+            *member = "abcdefg".into();
+        }
+        assert_eq!(c.len(), 2);
+        assert_eq!(c.get_two("asdfsadfsadf").unwrap(), ("abcdefg".into(), None));
+    }
+
+    #[test]
+    fn test_get_n() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        c.add("opqrstu");
+        c.add("hijklmn");
+        let res = c.get_n("asdfsadfsadf", 3);
+        assert!(res.is_ok());
+        let mut res = res.unwrap();
+        res.sort();
+        assert_eq!(res, vec!["abcdefg", "hijklmn", "opqrstu"]);
+    }
+    #[test]
+    fn test_get_n_more_than_available() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        c.add("opqrstu");
+        c.add("hijklmn");
+        let res = c.get_n("asdfsadfsadf", 4);
+        assert!(res.is_ok());
+        let mut res = res.unwrap();
+        res.sort();
+        assert_eq!(res, vec!["abcdefg", "hijklmn", "opqrstu"]);
+    }
+
+    #[test]
+    fn test_get_n_more_than_available_with_repeats() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        c.add("opqrstu");
+        c.add("hijklmn");
+        let res = c.get_n("asdfsadfsadf", 5);
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_set() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        c.add("opqrstu");
+        c.add("hijklmn");
+        c.set(vec!["qwer", "asdf"]);
+        assert_eq!(c.circle.len(), 40);
+        assert_eq!(c.sorted_hashes.read().len(), 40);
+    }
+
+    #[test]
+    fn test_with_hasher() {
+        struct ConstHasher;
+
+        impl Hasher32 for ConstHasher {
+            fn hash32(&self, _key: &[u8]) -> u32 {
+                42
+            }
+        }
+
+        let c = Consistent::with_hasher(ConstHasher);
+        c.add("abcdefg");
+        assert_eq!(c.circle.len(), 1);
+        assert_eq!(*c.sorted_hashes.read(), vec![(42, "abcdefg".into())]);
+        assert_eq!(c.get("asdfsadfsadf").unwrap(), "abcdefg");
+    }
+
+    #[test]
+    fn test_add_weighted() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        c.add_weighted("qwer", 3);
+        assert_eq!(c.circle.len(), 80);
+        assert_eq!(c.sorted_hashes.read().len(), 80);
+        c.remove("qwer");
+        assert_eq!(c.circle.len(), 20);
+        assert_eq!(c.sorted_hashes.read().len(), 20);
+    }
+
+    #[test]
+    fn test_contains() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        assert!(c.contains("abcdefg"));
+        assert!(!c.contains("qwer"));
+        c.remove("abcdefg");
+        assert!(!c.contains("abcdefg"));
+    }
+
+    #[test]
+    fn test_len() {
+        let c = Consistent::new();
+        assert!(c.is_empty());
+        c.add("abcdefg");
+        c.add("abcdefg");
+        c.add_weighted("qwer", 3);
+        assert_eq!(c.len(), 2);
+        c.remove("abcdefghijk");
+        assert_eq!(c.len(), 2);
+        c.remove("abcdefg");
+        c.remove("qwer");
+        assert_eq!(c.len(), 0);
+        assert!(c.is_empty());
+    }
+
+    #[test]
+    fn test_sorted_hashes_incremental() {
+        let c = Consistent::new();
+        for i in 0..50 {
+            c.add(format!("cache{i}"));
+        }
+        for i in (0..50).step_by(3) {
+            c.remove(format!("cache{i}"));
+        }
+        c.add_weighted("cache1", 2);
+        c.add_weighted("cache1", 1);
+        let mut expected = c
+            .circle
+            .iter()
+            .map(|k| (*k.key(), k.value().clone()))
+            .collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(*c.sorted_hashes.read(), expected);
+    }
+
+    #[test]
+    fn test_add_many() {
+        let a = Consistent::new();
+        let b = Consistent::new();
+        for elt in ["abcdefg", "opqrstu", "hijklmn"] {
+            a.add(elt);
+        }
+        b.add_many(["abcdefg", "opqrstu", "hijklmn", "abcdefg"]);
+        assert_eq!(b.len(), 3);
+        assert_eq!(b.circle.len(), 60);
+        assert_eq!(*b.sorted_hashes.read(), *a.sorted_hashes.read());
+    }
+
+    #[test]
+    fn test_get_iter() {
+        let c = Consistent::new();
+        assert_eq!(c.get_iter("asdfsadfsadf").count(), 0);
+        c.add("abcdefg");
+        c.add("opqrstu");
+        c.add("hijklmn");
+        let res = c.get_iter("asdfsadfsadf").collect::<Vec<_>>();
+        assert_eq!(res, c.get_n("asdfsadfsadf", 3).unwrap());
+        let (a, b) = c.get_two("asdfsadfsadf").unwrap();
+        assert_eq!(
+            c.get_iter("asdfsadfsadf").take(2).collect::<Vec<_>>(),
+            vec![a, b.unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_clear() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        c.add("opqrstu");
+        c.clear();
+        assert!(c.is_empty());
+        assert_eq!(c.circle.len(), 0);
+        assert_eq!(c.sorted_hashes.read().len(), 0);
+        assert!(matches!(c.get("asdfsadfsadf"), Err(Error::EmptyCircle)));
+    }
+
+    #[test]
+    fn test_consistent64() {
+        let c = Consistent64::with_hasher(FxHasher64);
+        c.add("abcdefg");
+        c.add("opqrstu");
+        c.add("hijklmn");
+        assert_eq!(c.circle.len(), 60);
+        assert!(c
+            .sorted_hashes
+            .read()
+            .iter()
+            .any(|(h, _)| *h > u32::MAX as u64));
+        let mut res = c.get_n("asdfsadfsadf", 3).unwrap();
+        res.sort();
+        assert_eq!(res, vec!["abcdefg", "hijklmn", "opqrstu"]);
+    }
+
+    #[test]
+    fn test_get_checked() {
+        // Collides every unsalted virtual node of the same length.
+        struct LenHasher;
+
+        impl Hasher32 for LenHasher {
+            fn hash32(&self, key: &[u8]) -> u32 {
+                if key.contains(&b'#') {
+                    FxHasher32.hash32(key) | 0xff
+                } else {
+                    key.len() as u32
+                }
+            }
+        }
+
+        let c = Consistent::with_hasher(LenHasher).with_number_of_replicas(1);
+        assert!(matches!(c.get_checked("x"), Err(Error::EmptyCircle)));
+        c.add("a");
+        assert_eq!(c.get_checked("x").unwrap(), ("a".into(), false));
+        c.add("b");
+        // "x" hashes to 1, just before the position both members hashed to.
+        assert_eq!(c.get_checked("x").unwrap(), ("a".into(), true));
+        assert_eq!(c.get_checked("xyz").unwrap(), ("b".into(), false));
+
+        // Once "b" is gone, nothing else contests the position "a" holds.
+        c.remove("b");
+        assert_eq!(c.get_checked("x").unwrap(), ("a".into(), false));
+        c.add("b");
+        assert_eq!(c.get_checked("x").unwrap(), ("a".into(), true));
+        // With a third contender, the position stays flagged until both of
+        // the others are gone.
+        c.add("c");
+        c.remove("c");
+        assert_eq!(c.get_checked("x").unwrap(), ("a".into(), true));
+        c.add("c");
+        c.remove("b");
+        assert_eq!(c.get_checked("x").unwrap(), ("a".into(), true));
+        c.remove("c");
+        assert_eq!(c.get_checked("x").unwrap(), ("a".into(), false));
+        c.add("b");
+
+        c.remove("a");
+        c.add("a");
+        assert_eq!(c.get_checked("x").unwrap(), ("a".into(), false));
+        c.add("c");
+        c.rebuild();
+        assert_eq!(c.get_checked("x").unwrap(), ("a".into(), true));
+        c.clear();
+        c.add("a");
+        assert_eq!(c.get_checked("x").unwrap(), ("a".into(), false));
+    }
+
+    #[test]
+    fn test_collisions_probe() {
+        // Collides every unsalted virtual node of the same length.
+        struct LenHasher;
+
+        impl Hasher32 for LenHasher {
+            fn hash32(&self, key: &[u8]) -> u32 {
+                if key.contains(&b'#') {
+                    FxHasher32.hash32(key)
+                } else {
+                    key.len() as u32
+                }
+            }
+        }
+
+        let c = Consistent::with_hasher(LenHasher).with_number_of_replicas(1);
+        c.add("a");
+        c.add("b");
+        assert_eq!(c.collision_count(), 1);
+        assert_eq!(c.circle.len(), 2);
+        assert_eq!(c.sorted_hashes.read().len(), 2);
+        c.add("b");
+        assert_eq!(c.circle.len(), 2);
+        c.remove("a");
+        assert_eq!(c.circle.len(), 1);
+        assert_eq!(c.get("asdfsadfsadf").unwrap(), "b");
+        c.remove("b");
+        assert_eq!(c.circle.len(), 0);
+        assert_eq!(c.sorted_hashes.read().len(), 0);
+    }
+
+    #[test]
+    fn test_replica_key_format() {
+        for (format, key) in [
+            (ReplicaKeyFormat::IndexPrefix, "0abcdefg"),
+            (ReplicaKeyFormat::MemberPrefix, "abcdefg0"),
+            (ReplicaKeyFormat::Ketama, "abcdefg-0"),
+        ] {
+            let c = Consistent::new()
+                .with_number_of_replicas(1)
+                .with_replica_key_format(format);
+            c.add("abcdefg");
+            assert_eq!(
+                *c.sorted_hashes.read(),
+                vec![(fxhash::hash32(key), "abcdefg".into())]
+            );
+        }
+    }
+
+    #[test]
+    fn test_node_socket_addr() {
+        let a: SocketAddr = "127.0.0.1:6379".parse().unwrap();
+        let b: SocketAddr = "127.0.0.1:6380".parse().unwrap();
+        let c = Consistent::<SocketAddr>::default();
+        c.add(a);
+        c.add(b);
+        assert!(c.contains("127.0.0.1:6379"));
+        let res = c.get("asdfsadfsadf").unwrap();
+        assert!(res == a || res == b);
+        c.remove("127.0.0.1:6379");
+        assert_eq!(c.len(), 1);
+        assert_eq!(c.circle.len(), 20);
+        assert_eq!(c.get("asdfsadfsadf").unwrap(), b);
+    }
+
+    #[test]
+    fn test_node_custom() {
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        struct NodeId(u16);
+
+        impl Node for NodeId {
+            fn node_key(&self) -> FastStr {
+                format!("node-{}", self.0).into()
+            }
+        }
+
+        let c = Consistent::<NodeId>::default();
+        c.add(NodeId(1));
+        c.add(NodeId(2));
+        c.add(NodeId(3));
+        let mut res = c.get_n("asdfsadfsadf", 3).unwrap();
+        res.sort_by_key(|n| n.0);
+        assert_eq!(res, vec![NodeId(1), NodeId(2), NodeId(3)]);
+        c.set(vec![NodeId(2)]);
+        assert_eq!(c.members(), vec![NodeId(2)]);
+    }
+
+    #[test]
+    fn test_get_detailed() {
+        let c = Consistent::new();
+        assert!(c.get_detailed("asdfsadfsadf").is_err());
+        c.add("abcdefg");
+        c.add("opqrstu");
+        let (member, hash) = c.get_detailed("asdfsadfsadf").unwrap();
+        assert_eq!(member, c.get("asdfsadfsadf").unwrap());
+        assert_eq!(*c.circle.get(&hash).unwrap(), member);
+        let key = fxhash::hash32("asdfsadfsadf");
+        let sorted_hashes = c.sorted_hashes.read();
+        assert!(hash > key || hash == sorted_hashes[0].0);
+    }
+
+    #[test]
+    fn test_with_hash_seed() {
+        let members = ["abcdefg", "opqrstu", "hijklmn"];
+        let unseeded = Consistent::new();
+        let zero = Consistent::new().with_hash_seed(0);
+        let a = Consistent::new().with_hash_seed(42);
+        let b = Consistent::new().with_hash_seed(42);
+        for c in [&unseeded, &zero, &a, &b] {
+            c.add_many(members);
+        }
+        assert_eq!(*zero.sorted_hashes.read(), *unseeded.sorted_hashes.read());
+        assert_ne!(*a.sorted_hashes.read(), *unseeded.sorted_hashes.read());
+        assert_eq!(*a.sorted_hashes.read(), *b.sorted_hashes.read());
+        for i in 0..100 {
+            let key = format!("user{i}");
+            assert_eq!(a.get(&key).unwrap(), b.get(&key).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_clone() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        c.add("opqrstu");
+        let d = c.clone();
+        assert_eq!(*d.sorted_hashes.read(), *c.sorted_hashes.read());
+        d.add("hijklmn");
+        d.remove("abcdefg");
+        assert_eq!(c.len(), 2);
+        assert!(c.contains("abcdefg"));
+        assert!(!c.contains("hijklmn"));
+        assert_eq!(c.circle.len(), 40);
+        assert_eq!(c.sorted_hashes.read().len(), 40);
+        assert_eq!(d.len(), 2);
+        assert!(d.contains("hijklmn"));
+    }
+
+    #[test]
+    fn test_disable() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        c.add("opqrstu");
+        c.add("hijklmn");
+        let order = c.get_n("asdfsadfsadf", 3).unwrap();
+        c.disable(&order[0]);
+        assert_eq!(c.circle.len(), 60);
+        assert_eq!(c.get("asdfsadfsadf").unwrap(), order[1]);
+        assert_eq!(
+            c.get_two("asdfsadfsadf").unwrap(),
+            (order[1].clone(), Some(order[2].clone()))
+        );
+        assert_eq!(c.get_n("asdfsadfsadf", 3).unwrap(), order[1..]);
+        c.disable(&order[1]);
+        c.disable(&order[2]);
+        assert!(matches!(c.get("asdfsadfsadf"), Err(Error::EmptyCircle)));
+        assert!(matches!(
+            c.get_n("asdfsadfsadf", 3),
+            Err(Error::EmptyCircle)
+        ));
+        c.enable(&order[0]);
+        c.enable(&order[1]);
+        c.enable(&order[2]);
+        assert_eq!(c.get_n("asdfsadfsadf", 3).unwrap(), order);
+    }
+
+    #[test]
+    fn test_get_excluding() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        c.add("opqrstu");
+        c.add("hijklmn");
+        let order = c.get_n("asdfsadfsadf", 3).unwrap();
+        let mut exclude = HashSet::new();
+        assert_eq!(c.get_excluding("asdfsadfsadf", &exclude).unwrap(), order[0]);
+        exclude.insert(order[0].clone());
+        assert_eq!(c.get_excluding("asdfsadfsadf", &exclude).unwrap(), order[1]);
+        exclude.insert(order[1].clone());
+        exclude.insert(order[2].clone());
+        assert!(matches!(
+            c.get_excluding("asdfsadfsadf", &exclude),
+            Err(Error::EmptyCircle)
+        ));
+        assert_eq!(c.get("asdfsadfsadf").unwrap(), order[0]);
+    }
+
+    #[test]
+    fn test_set_number_of_replicas() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        c.add_weighted("opqrstu", 2);
+        assert_eq!(c.number_of_replicas(), 20);
+        c.set_number_of_replicas(40);
+        assert_eq!(c.number_of_replicas(), 40);
+        assert_eq!(c.len(), 2);
+        assert_eq!(c.circle.len(), 120);
+        assert_eq!(c.sorted_hashes.read().len(), 120);
+        assert_eq!(*c.replicas.get("opqrstu").unwrap(), 80);
+        let d = Consistent::new().with_number_of_replicas(40);
+        d.add("abcdefg");
+        d.add_weighted("opqrstu", 2);
+        assert_eq!(*d.sorted_hashes.read(), *c.sorted_hashes.read());
+    }
+
+    #[test]
+    fn test_set_number_of_replicas_restores_counts() {
+        let c: Consistent = Consistent::from_replica_counts([("abcdefg", 7), ("opqrstu", 20)]);
+        let before = c.sorted_hashes.read().clone();
+        c.set_number_of_replicas(1);
+        assert_eq!(*c.replicas.get("abcdefg").unwrap(), 1);
+        assert_eq!(*c.replicas.get("opqrstu").unwrap(), 1);
+        assert!(c.get("user_mcnulty").is_ok());
+        c.set_number_of_replicas(20);
+        assert_eq!(*c.replicas.get("abcdefg").unwrap(), 7);
+        assert_eq!(*c.sorted_hashes.read(), before);
+    }
+
+    #[test]
+    fn test_try_remove() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        assert!(matches!(
+            c.try_remove("abcdefghijk"),
+            Err(Error::MemberNotFound(elt)) if elt == "abcdefghijk"
+        ));
+        assert_eq!(c.circle.len(), 20);
+        assert!(c.try_remove("abcdefg").is_ok());
+        assert_eq!(c.circle.len(), 0);
+        assert!(c.try_remove("abcdefg").is_err());
+    }
+
+    #[test]
+    fn test_get_bytes() {
+        for key in ["", "a", "user_mcnulty", "héllo wörld"] {
+            assert_eq!(FxHasher32.hash32(key.as_bytes()), fxhash::hash32(key));
+            assert_eq!(FxHasher64.hash(key.as_bytes()), fxhash::hash64(key));
+        }
+
+        let c = Consistent::new();
+        c.add_bytes(b"cacheA").unwrap();
+        c.add("cacheB");
+        c.add("cacheC");
+        for key in ["user_mcnulty", "user_bunk", "user_omar"] {
+            assert_eq!(c.get_bytes(key.as_bytes()).unwrap(), c.get(key).unwrap());
+        }
+        assert!(c.get_bytes(&[0xde, 0xad, 0xbe, 0xef]).is_ok());
+
+        assert!(matches!(
+            c.add_bytes(&[0xff, 0xfe]),
+            Err(Error::InvalidUtf8(_))
+        ));
+        c.remove_bytes(&[0xff, 0xfe]);
+        c.remove_bytes(b"cacheA");
+        assert_eq!(c.len(), 2);
+        assert!(!c.contains("cacheA"));
+    }
+
+    #[test]
+    fn test_from_iter_extend() {
+        let c: Consistent = ["abcdefg", "hijklmn"].into_iter().collect();
+        assert_eq!(c.len(), 2);
+        assert_eq!(c.sorted_hashes.read().len(), 40);
+
+        let mut r = &c;
+        r.extend(["opqrstu", "abcdefg"]);
+        assert_eq!(c.len(), 3);
+        assert_eq!(c.sorted_hashes.read().len(), 60);
+
+        let ring = Consistent::new();
+        ring.add("abcdefg");
+        ring.add("hijklmn");
+        ring.add("opqrstu");
+        assert_eq!(*c.sorted_hashes.read(), *ring.sorted_hashes.read());
+    }
+
+    #[test]
+    fn test_get_n_excluding() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        c.add("hijklmn");
+        c.add("opqrstu");
+        let all = c.get_n("asdfsadfsadf", 3).unwrap();
+        let exclude = HashSet::from([all[0].clone()]);
+        assert_eq!(
+            c.get_n_excluding("asdfsadfsadf", 3, &exclude).unwrap(),
+            all[1..]
+        );
+        assert_eq!(
+            c.get_n_excluding("asdfsadfsadf", 1, &exclude).unwrap(),
+            all[1..2]
+        );
+        assert_eq!(
+            c.get_n_excluding("asdfsadfsadf", 3, &HashSet::new())
+                .unwrap(),
+            all
+        );
+        let exclude = all.iter().cloned().collect::<HashSet<_>>();
+        assert!(c
+            .get_n_excluding("asdfsadfsadf", 3, &exclude)
+            .unwrap()
+            .is_empty());
+        assert_eq!(c.len(), 3);
+    }
+
+    #[test]
+    fn test_concurrent_lookups() {
+        let c = Consistent::new();
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for _ in 0..500 {
+                    c.add("abcdefg");
+                    c.add_many(["hijklmn", "opqrstu"]);
+                    c.remove("abcdefg");
+                    c.remove("hijklmn");
+                    c.remove("opqrstu");
+                }
+            });
+            for _ in 0..2 {
+                s.spawn(|| {
+                    for i in 0..5000 {
+                        let key = format!("user{i}");
+                        let _ = c.get(&key);
+                        let _ = c.get_two(&key);
+                        let _ = c.get_n(&key, 2);
+                        let _ = c.get_detailed(&key);
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn test_with_capacity() {
+        let c = Consistent::new()
+            .with_number_of_replicas(10)
+            .with_capacity(100);
+        assert!(c.circle.capacity() >= 1000);
+        assert!(c.sorted_hashes.read().capacity() >= 1000);
+        c.add_many((0..100).map(|i| format!("node{i}")));
+        assert_eq!(c.len(), 100);
+        assert_eq!(c.sorted_hashes.read().len(), 1000);
+    }
+
+    #[test]
+    fn test_replace() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        c.add_weighted("hijklmn", 2);
+        c.replace("hijklmn", "opqrstu");
+        assert_eq!(c.len(), 2);
+        assert!(!c.contains("hijklmn"));
+
+        let expected = Consistent::new();
+        expected.add("abcdefg");
+        expected.add_weighted("opqrstu", 2);
+        assert_eq!(*c.sorted_hashes.read(), *expected.sorted_hashes.read());
+
+        c.replace("missing", "uvwxyz");
+        assert_eq!(c.len(), 3);
+        assert_eq!(c.sorted_hashes.read().len(), 80);
+    }
+
+    #[test]
+    fn test_set_number_of_replicas_keeps_placements() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        c.add("opqrstu");
+        let before = c.sorted_hashes.read().clone();
+        c.set_number_of_replicas(30);
+        let after = c.sorted_hashes.read().clone();
+        assert_eq!(after.len(), 60);
+        assert!(before.iter().all(|hash| after.contains(hash)));
+
+        c.set_number_of_replicas(10);
+        let d = Consistent::new().with_number_of_replicas(10);
+        d.add("abcdefg");
+        d.add("opqrstu");
+        assert_eq!(*c.sorted_hashes.read(), *d.sorted_hashes.read());
+        assert!(c
+            .sorted_hashes
+            .read()
+            .iter()
+            .all(|hash| before.contains(hash)));
+    }
+
+    #[test]
+    fn test_get_replicas() {
+        let c = Consistent::new();
+        assert!(matches!(c.get_replicas("key", 2), Err(Error::EmptyCircle)));
+        c.add("abcdefg");
+        c.add("hijklmn");
+        c.add("opqrstu");
+        for i in 0..100 {
+            let key = format!("user{i}");
+            let r = c.get_replicas(&key, 3).unwrap();
+            assert_eq!(r.primary, c.get(&key).unwrap());
+            let all = c.get_n(&key, 3).unwrap();
+            assert_eq!(r.replicas, all[1..]);
+        }
+        let r = c.get_replicas("user", 0).unwrap();
+        assert!(r.replicas.is_empty());
+    }
+
+    #[test]
+    fn test_remove_many() {
+        let c = Consistent::new();
+        c.add_many(["abcdefg", "hijklmn", "opqrstu"]);
+        c.disable("hijklmn");
+        c.remove_many(["hijklmn", "opqrstu", "missing"]);
+        assert_eq!(c.len(), 1);
+        assert_eq!(c.circle.len(), 20);
+        assert_eq!(c.sorted_hashes.read().len(), 20);
+        assert!(c.disabled.is_empty());
+        assert_eq!(c.get("user_mcnulty").unwrap(), "abcdefg");
+
+        // Nothing is rebuilt when no member is removed, so a layout that
+        // drifted from the circle stays as it is.
+        c.circle.remove(&c.sorted_hashes.read()[0].0);
+        c.remove_many(["missing"]);
+        assert_eq!(c.sorted_hashes.read().len(), 20);
+        c.remove_many(["abcdefg"]);
+        assert!(c.sorted_hashes.read().is_empty());
+    }
+
+    #[test]
+    fn test_add_remove_reporting() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        let added = c.add_reporting("hijklmn");
+        assert_eq!(added, c.virtual_nodes("hijklmn"));
+        assert!(c.add_reporting("hijklmn").is_empty());
+        assert!(c.remove_reporting("missing").is_empty());
+        assert_eq!(c.remove_reporting("hijklmn"), added);
+        assert_eq!(c.sorted_hashes.read().len(), 20);
+
+        let c = Consistent::new().with_number_of_replicas(10);
+        c.add_weighted("abcdefg", 2);
+        let dropped = c.virtual_nodes("abcdefg");
+        let changed = c.add_reporting("abcdefg");
+        assert_eq!(changed.len(), 10);
+        assert!(changed.iter().all(|hash| dropped.contains(hash)));
+        assert!(changed.iter().all(|hash| !c.circle.contains_key(hash)));
+    }
+
+    #[test]
+    fn test_remove_where() {
+        let c = Consistent::new();
+        c.add_many(["rack3-a", "rack1-a", "rack3-b", "rack2-a"]);
+        c.disable("rack3-b");
+        assert_eq!(c.remove_where(|key| key.starts_with("rack3-")), 2);
+        assert_eq!(c.len(), 2);
+        assert_eq!(c.sorted_hashes.read().len(), 40);
+        assert!(c.disabled.is_empty());
+        assert_eq!(c.remove_where(|key| key.starts_with("rack3-")), 0);
+        assert_eq!(c.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_get_ref() {
+        let c = Consistent::new();
+        assert!(matches!(c.get_ref("user_mcnulty"), Err(Error::EmptyCircle)));
+        c.add_many(["abcdefg", "hijklmn", "opqrstu"]);
+        for i in 0..100 {
+            let key = format!("user{i}");
+            assert_eq!(*c.get_ref(&key).unwrap(), c.get(&key).unwrap());
+        }
+        let owner = c.get("user_mcnulty").unwrap();
+        c.disable(&owner);
+        let member = c.get_ref("user_mcnulty").unwrap();
+        assert_ne!(*member, owner);
+        assert_eq!(format!("{member:?}"), format!("{:?}", *member));
+    }
+
+    #[test]
+    fn test_position_of() {
+        let c = Consistent::new();
+        assert!(matches!(c.position_of("key"), Err(Error::EmptyCircle)));
+        assert!(c.member_at(0).is_none());
+        c.add("abcdefg");
+        c.add("hijklmn");
+        for i in 0..100 {
+            let key = format!("user{i}");
+            let j = c.position_of(&key).unwrap();
+            assert!(j < 40);
+            assert_eq!(c.member_at(j).unwrap(), c.get(&key).unwrap());
+            assert_eq!(c.member_at(j + 40), c.member_at(j));
+        }
+
+        c.disable("abcdefg");
+        for i in 0..100 {
+            let key = format!("user{i}");
+            assert_eq!(
+                c.member_at(c.position_of(&key).unwrap()).unwrap(),
+                "hijklmn"
+            );
+        }
+        c.disable("hijklmn");
+        assert!(matches!(c.position_of("key"), Err(Error::EmptyCircle)));
+    }
+
+    #[test]
+    fn test_get_with() {
+        let c = Consistent::<Arc<str>>::default();
+        assert!(matches!(c.get_with("key", |_| ()), Err(Error::EmptyCircle)));
+        c.add("abcdefg");
+        c.add("hijklmn");
+        for i in 0..100 {
+            let key = format!("user{i}");
+            let owner = c.get(&key).unwrap();
+            assert_eq!(c.get_with(&key, |v| v.len()).unwrap(), owner.len());
+            assert!(c.get_with(&key, |v| Arc::ptr_eq(v, &owner)).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_get_where() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        c.add("hijklmn");
+        c.add("opqrstu");
+        let owner = c.get("user_mcnulty").unwrap();
+        assert_eq!(c.get_where("user_mcnulty", |_| true).unwrap(), owner);
+        let next = c.get_where("user_mcnulty", |v| *v != owner).unwrap();
+        assert_ne!(next, owner);
+        assert_eq!(next, c.get_n("user_mcnulty", 2).unwrap()[1]);
+        assert!(matches!(
+            c.get_where("user_mcnulty", |_| false),
+            Err(Error::EmptyCircle)
+        ));
+    }
+
+    #[test]
+    fn test_collision_policy_overwrite() {
+        // Collides every unsalted virtual node of the same length.
+        struct LenHasher;
+
+        impl Hasher32 for LenHasher {
+            fn hash32(&self, key: &[u8]) -> u32 {
+                key.len() as u32
+            }
+        }
+
+        let c = Consistent::with_hasher(LenHasher)
+            .with_number_of_replicas(1)
+            .with_collision_policy(CollisionPolicy::Overwrite);
+        c.add("a");
+        c.add("b");
+        assert_eq!(c.collision_count(), 1);
+        assert_eq!(c.circle.len(), 1);
+        assert_eq!(*c.sorted_hashes.read(), vec![(2, "b".into())]);
+        assert_eq!(c.get("x").unwrap(), "b");
+        c.remove("a");
+        assert_eq!(c.get("x").unwrap(), "b");
+        c.remove("b");
+        assert!(c.circle.is_empty());
+        assert!(c.sorted_hashes.read().is_empty());
+    }
+
+    #[test]
+    fn test_observer() {
+        #[derive(Default)]
+        struct Recorder(Mutex<Vec<String>>);
+
+        impl RingObserver for Recorder {
+            fn on_add(&self, member: &str) {
+                self.0.lock().unwrap().push(format!("add {member}"));
+            }
+
+            fn on_remove(&self, member: &str) {
+                self.0.lock().unwrap().push(format!("remove {member}"));
+            }
+
+            fn on_get(&self, key: &str, chosen: &str) {
+                self.0.lock().unwrap().push(format!("get {key} {chosen}"));
+            }
+
+            fn on_get_bytes(&self, key: &[u8], chosen: &str) {
+                self.0.lock().unwrap().push(format!("get {key:?} {chosen}"));
+            }
+        }
+
+        let recorder = Arc::new(Recorder::default());
+        let c = Consistent::new().with_observer(recorder.clone());
+        c.add("abcdefg");
+        c.add_weighted("abcdefg", 2);
+        c.get("user_mcnulty").unwrap();
+        c.get_bytes(b"user_omar").unwrap();
+        c.get_bytes(&[0xff, 0xfe]).unwrap();
+        c.get_bounded("user_bunk", &LoadTracker::new(), 0.25)
+            .unwrap();
+        c.remove("abcdefg");
+        c.remove("abcdefg");
+        c.add("hijklmn");
+        c.clear();
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            [
+                "add abcdefg",
+                "add abcdefg",
+                "get user_mcnulty abcdefg",
+                "get user_omar abcdefg",
+                "get [255, 254] abcdefg",
+                "get user_bunk abcdefg",
+                "remove abcdefg",
+                "add hijklmn",
+                "remove hijklmn",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_observer_sees_added_member() {
+        #[derive(Default)]
+        struct Checker {
+            ring: OnceLock<Weak<Consistent>>,
+            seen: Mutex<Vec<bool>>,
+        }
+
+        impl RingObserver for Checker {
+            fn on_add(&self, member: &str) {
+                let ring = self.ring.get().unwrap().upgrade().unwrap();
+                self.seen.lock().unwrap().push(ring.contains(member));
+            }
+        }
+
+        let checker = Arc::new(Checker::default());
+        let c = Arc::new(Consistent::new().with_observer(checker.clone()));
+        checker.ring.set(Arc::downgrade(&c)).unwrap();
+        c.add("abcdefg");
+        c.add("hijklmn");
+        assert_eq!(*checker.seen.lock().unwrap(), [true, true]);
     }
 
     #[test]
-    fn test_get_n_more_than_available_with_repeats() {
+    fn test_get_n_strict() {
         let c = Consistent::new();
+        assert!(matches!(c.get_n_strict("key", 1), Err(Error::EmptyCircle)));
         c.add("abcdefg");
-        c.add("opqrstu");
         c.add("hijklmn");
-        let res = c.get_n("asdfsadfsadf", 5);
-        assert!(res.is_ok());
-        assert_eq!(res.unwrap().len(), 3);
+        assert_eq!(
+            c.get_n_strict("key", 2).unwrap(),
+            c.get_n("key", 2).unwrap()
+        );
+        assert!(matches!(
+            c.get_n_strict("key", 3),
+            Err(Error::InsufficientMembers {
+                requested: 3,
+                available: 2
+            })
+        ));
+        c.disable("hijklmn");
+        assert!(matches!(
+            c.get_n_strict("key", 2),
+            Err(Error::InsufficientMembers { available: 1, .. })
+        ));
     }
 
     #[test]
-    fn test_set() {
+    fn test_from_weighted() {
+        let weights = HashMap::from([("abcdefg".to_string(), 1), ("opqrstu".to_string(), 3)]);
+        let c: Consistent = Consistent::from_weighted(weights, 10);
+        assert_eq!(c.len(), 2);
+        assert_eq!(c.number_of_replicas(), 10);
+        assert_eq!(*c.replicas.get("opqrstu").unwrap(), 30);
+
+        let d = Consistent::new().with_number_of_replicas(10);
+        d.add("abcdefg");
+        d.add_weighted("opqrstu", 3);
+        assert_eq!(*c.sorted_hashes.read(), *d.sorted_hashes.read());
+    }
+
+    #[test]
+    fn test_from_replica_counts() {
+        let c: Consistent = Consistent::from_replica_counts([("abcdefg", 40), ("opqrstu", 10)]);
+        assert_eq!(c.len(), 2);
+        assert_eq!(c.virtual_nodes("abcdefg").len(), 40);
+        assert_eq!(c.virtual_nodes("opqrstu").len(), 10);
+        assert_eq!(c.validate(), Ok(()));
+        c.set_number_of_replicas(40);
+        assert_eq!(c.virtual_nodes("abcdefg").len(), 80);
+        assert_eq!(c.virtual_nodes("opqrstu").len(), 20);
+        c.set_number_of_replicas(20);
+        assert_eq!(c.virtual_nodes("abcdefg").len(), 40);
+        c.remove("abcdefg");
+        assert_eq!(c.sorted_hashes.read().len(), 10);
+        assert_eq!(c.circle.len(), 10);
+    }
+
+    #[test]
+    fn test_validate() {
+        let c = Arc::new(Consistent::new().with_collision_policy(CollisionPolicy::Overwrite));
+        assert_eq!(c.validate(), Ok(()));
+        let handles = (0..4)
+            .map(|t| {
+                let c = c.clone();
+                std::thread::spawn(move || {
+                    for i in 0..50 {
+                        let elt = format!("cache{}", (i * 7 + t) % 20);
+                        match i % 4 {
+                            0 => c.add(elt),
+                            1 => c.add_weighted(elt, 2),
+                            2 => c.remove(elt),
+                            _ => c.set_number_of_replicas(10 + i % 3),
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(c.validate(), Ok(()));
+
+        c.circle.remove(&c.sorted_hashes.read()[0].0);
+        assert!(c.validate().unwrap_err().contains("circle has"));
+    }
+
+    #[test]
+    fn test_get_n_weighted() {
+        let c = Consistent::new();
+        c.add_weighted("heavy", 100);
+        for i in 0..4 {
+            c.add(format!("light{i}"));
+        }
+        for i in 0..200 {
+            let key = format!("user{i}");
+            let res = c.get_n(&key, 3).unwrap();
+            assert_eq!(res.len(), 3);
+            assert_eq!(res[0], c.get(&key).unwrap());
+            assert_eq!(res, c.get_iter(&key).take(3).collect::<Vec<_>>());
+        }
+
+        c.disable("light0");
+        let exclude = HashSet::from([FastStr::new("light1")]);
+        let res = c.get_n_excluding("user_mcnulty", 5, &exclude).unwrap();
+        let mut res = res.iter().map(|m| m.as_str()).collect::<Vec<_>>();
+        res.sort();
+        assert_eq!(res, vec!["heavy", "light2", "light3"]);
+    }
+
+    #[test]
+    fn test_get_prev() {
         let c = Consistent::new();
+        assert!(matches!(
+            c.get_prev("user_mcnulty"),
+            Err(Error::EmptyCircle)
+        ));
         c.add("abcdefg");
-        c.add("opqrstu");
+        assert_eq!(c.get_prev("user_mcnulty").unwrap(), "abcdefg");
         c.add("hijklmn");
-        c.set(vec!["qwer", "asdf"]);
+        c.add("opqrstu");
+        let entries = c.ring_entries();
+        for i in 0..200 {
+            let key = format!("user{i}");
+            let owner = c.get(&key).unwrap();
+            let prev = c.get_prev(&key).unwrap();
+            assert_ne!(prev, owner);
+            let i = entries.partition_point(|(h, _)| *h <= fxhash::hash32(&key));
+            let behind = entries[..i]
+                .iter()
+                .rev()
+                .chain(entries[i..].iter().rev())
+                .find(|(_, m)| *m != owner)
+                .unwrap();
+            assert_eq!(prev, behind.1);
+        }
+
+        let prev = c.get_prev("user_mcnulty").unwrap();
+        c.disable(&prev);
+        assert_ne!(c.get_prev("user_mcnulty").unwrap(), prev);
+    }
+
+    #[test]
+    fn test_set_is_atomic() {
+        let old = (0..50).map(|i| format!("cache{i}")).collect::<Vec<_>>();
+        let new = (10..60).map(|i| format!("cache{i}")).collect::<Vec<_>>();
+        let c = Arc::new(Consistent::new());
+        c.set(old.clone());
+        let reader = {
+            let c = c.clone();
+            std::thread::spawn(move || {
+                for _ in 0..2000 {
+                    assert_eq!(c.get_n("user_mcnulty", 100).unwrap().len(), 50);
+                }
+            })
+        };
+        for _ in 0..20 {
+            c.set(new.clone());
+            c.set(old.clone());
+        }
+        reader.join().unwrap();
+
+        c.set(new.clone());
+        let expected = Consistent::new();
+        expected.add_many(new);
+        assert_eq!(*c.sorted_hashes.read(), *expected.sorted_hashes.read());
+        assert_eq!(c.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_debug() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        c.add_weighted("hijklmn", 2);
+        c.disable("abcdefg");
+        assert_eq!(
+            format!("{c:?}"),
+            "Consistent { len: 2, number_of_replicas: 20, virtual_nodes: 60, disabled: 1, \
+             replicas: {\"abcdefg\": 20, \"hijklmn\": 40}, .. }"
+        );
+
+        c.add_many((0..30).map(|i| format!("cache{i:02}")));
+        let debug = format!("{c:?}");
+        assert!(debug.contains("\"cache18\": 20, ..}"), "{debug}");
+        assert!(!debug.contains("cache19"));
+        assert!(debug.len() < 1000);
+    }
+
+    #[test]
+    fn test_add_if_absent() {
+        let c = Consistent::new();
+        c.add_weighted("abcdefg", 2);
+        assert!(!c.add_if_absent("abcdefg"));
         assert_eq!(c.circle.len(), 40);
-        assert_eq!(c.sorted_hashes.read().len(), 40);
+        assert!(c.add_if_absent("hijklmn"));
+        assert!(!c.add_if_absent("hijklmn"));
+        assert_eq!(c.len(), 2);
+        assert_eq!(c.sorted_hashes.read().len(), 60);
+
+        let d = Consistent::new();
+        d.add_weighted("abcdefg", 2);
+        d.add("hijklmn");
+        assert_eq!(*c.sorted_hashes.read(), *d.sorted_hashes.read());
+    }
+
+    #[test]
+    fn test_virtual_node_count() {
+        let c = Consistent::new();
+        assert_eq!(c.virtual_node_count(), 0);
+        c.add("abcdefg");
+        c.add_weighted("hijklmn", 3);
+        assert_eq!(c.virtual_node_count(), 80);
+        c.remove("abcdefg");
+        assert_eq!(c.virtual_node_count(), 60);
+
+        struct ConstHasher;
+
+        impl Hasher32 for ConstHasher {
+            fn hash32(&self, _key: &[u8]) -> u32 {
+                42
+            }
+        }
+
+        let c = Consistent::with_hasher(ConstHasher);
+        c.add("abcdefg");
+        assert_eq!(c.virtual_node_count(), 1);
+        assert!(c.virtual_node_count() < c.len() * c.number_of_replicas());
+    }
+
+    #[test]
+    fn test_zero_replicas() {
+        let c = Consistent::new().with_number_of_replicas(0);
+        assert_eq!(c.number_of_replicas(), 1);
+        c.add("abcdefg");
+        assert_eq!(c.virtual_node_count(), 1);
+        assert_eq!(c.get("user_mcnulty").unwrap(), "abcdefg");
+
+        c.set_number_of_replicas(0);
+        assert_eq!(c.number_of_replicas(), 1);
+        assert_eq!(c.get("user_mcnulty").unwrap(), "abcdefg");
+
+        let c: Consistent = Consistent::from_weighted([("abcdefg", 2)], 0);
+        assert_eq!(c.virtual_node_count(), 2);
+    }
+
+    #[test]
+    fn test_zero_weight() {
+        let c = Consistent::new();
+        c.add_weighted("abcdefg", 0);
+        assert_eq!(c.virtual_node_count(), 20);
+        assert_eq!(c.get("user_mcnulty").unwrap(), "abcdefg");
+
+        let c = Consistent::new();
+        assert!(matches!(
+            c.add_weighted_str("abcdefg", "0"),
+            Err(Error::InvalidWeight(_))
+        ));
+        assert!(c.is_empty());
+
+        let c: Consistent = Consistent::from_weighted([("abcdefg", 0)], 10);
+        assert_eq!(c.virtual_node_count(), 10);
+        assert_eq!(c.get("user_mcnulty").unwrap(), "abcdefg");
+
+        let c: Consistent = Consistent::from_replica_counts([("abcdefg", 0)]);
+        assert_eq!(c.virtual_node_count(), 1);
+        assert_eq!(c.get("user_mcnulty").unwrap(), "abcdefg");
+    }
+
+    #[test]
+    fn test_get_by_hash() {
+        let c = Consistent::new();
+        assert!(matches!(c.get_by_hash(0), Err(Error::EmptyCircle)));
+        c.add("abcdefg");
+        c.add("hijklmn");
+        c.add("opqrstu");
+        for i in 0..100 {
+            let key = format!("user{i}");
+            let hash = FxHasher32.hash32(key.as_bytes());
+            assert_eq!(c.get_by_hash(hash).unwrap(), c.get(&key).unwrap());
+        }
+        // Past the last virtual node, lookups wrap around to the first.
+        let entries = c.ring_entries();
+        assert_eq!(c.get_by_hash(u32::MAX).unwrap(), entries[0].1);
+        assert_eq!(c.get_by_hash(entries[0].0 - 1).unwrap(), entries[0].1);
+    }
+
+    #[test]
+    fn test_diff() {
+        let running = Consistent::new();
+        running.add_many(["abcdefg", "hijklmn", "opqrstu"]);
+        let next = Consistent::with_hasher(FxHasher64);
+        next.add_many(["uvwxyz", "hijklmn", "abcdefg", "qwer"]);
+        assert_eq!(
+            running.diff(&next),
+            RingDiff {
+                added: vec!["qwer".into(), "uvwxyz".into()],
+                removed: vec!["opqrstu".into()],
+            }
+        );
+        let empty = running.diff(&running.clone());
+        assert!(empty.added.is_empty() && empty.removed.is_empty());
+    }
+
+    #[test]
+    fn test_keep_lowest_collision_policy() {
+        #[derive(Default)]
+        struct LenHasher;
+
+        impl Hasher32 for LenHasher {
+            fn hash32(&self, key: &[u8]) -> u32 {
+                key.len() as u32
+            }
+        }
+
+        let build = |members: [&'static str; 2]| {
+            let c = Consistent::with_hasher(LenHasher)
+                .with_number_of_replicas(1)
+                .with_collision_policy(CollisionPolicy::KeepLowest);
+            for m in members {
+                c.add(m);
+            }
+            c
+        };
+        let ab = build(["a", "b"]);
+        let ba = build(["b", "a"]);
+        assert_eq!(ab.collision_count(), 1);
+        assert_eq!(ab.get("x").unwrap(), "a");
+        assert_eq!(*ab.sorted_hashes.read(), vec![(2, "a".into())]);
+        assert_eq!(*ab.sorted_hashes.read(), *ba.sorted_hashes.read());
+        assert_eq!(ab.validate(), Ok(()));
+
+        let bytes = ba.to_bytes();
+        let d: Consistent<FastStr, LenHasher> = Consistent::from_bytes(&bytes).unwrap();
+        assert_eq!(d.collision_policy, CollisionPolicy::KeepLowest);
+        d.add("b");
+        assert_eq!(*d.sorted_hashes.read(), *ab.sorted_hashes.read());
+    }
+
+    #[test]
+    fn test_add_weighted_str() {
+        let c = Consistent::new();
+        c.add_weighted_str("abcdefg", "4x").unwrap();
+        c.add_weighted_str("hijklmn", " 200% ").unwrap();
+        c.add_weighted_str("opqrstu", "0.5").unwrap();
+        c.add_weighted_str("uvwxyz", "3").unwrap();
+        c.add_weighted_str("zzzzzzz", "1%").unwrap();
+        assert_eq!(*c.replicas.get("abcdefg").unwrap(), 80);
+        assert_eq!(*c.replicas.get("hijklmn").unwrap(), 40);
+        assert_eq!(*c.replicas.get("opqrstu").unwrap(), 10);
+        assert_eq!(*c.replicas.get("uvwxyz").unwrap(), 60);
+        assert_eq!(*c.replicas.get("zzzzzzz").unwrap(), 1);
+        assert_eq!(c.sorted_hashes.read().len(), 191);
+
+        for spec in [
+            "", "x", "four", "-1", "1.5y", "inf", "NaN%", "0", "0x", "0%",
+        ] {
+            assert!(matches!(
+                c.add_weighted_str("missing", spec),
+                Err(Error::InvalidWeight(s)) if s == spec
+            ));
+        }
+        assert!(!c.contains("missing"));
+
+        c.add_weighted_str("opqrstu", "0.33").unwrap();
+        assert_eq!(*c.replicas.get("opqrstu").unwrap(), 7);
+        c.set_number_of_replicas(1);
+        assert_eq!(*c.replicas.get("opqrstu").unwrap(), 1);
+        c.set_number_of_replicas(100);
+        assert_eq!(*c.replicas.get("opqrstu").unwrap(), 33);
+        c.set_number_of_replicas(20);
+        assert_eq!(*c.replicas.get("opqrstu").unwrap(), 7);
+        assert_eq!(*c.replicas.get("hijklmn").unwrap(), 40);
+    }
+
+    #[test]
+    fn test_members_sorted() {
+        let c = Consistent::new();
+        assert!(c.members_sorted().is_empty());
+        c.add_many(["opqrstu", "abcdefg", "uvwxyz", "hijklmn"]);
+        assert_eq!(
+            c.members_sorted(),
+            vec!["abcdefg", "hijklmn", "opqrstu", "uvwxyz"]
+        );
+    }
+
+    #[test]
+    fn test_members_iter() {
+        let c = Consistent::new();
+        assert_eq!(c.members_iter().count(), 0);
+        c.add_many(["abcdefg", "hijklmn", "opqrstu"]);
+        let mut members = c.members_iter().collect::<Vec<_>>();
+        members.sort();
+        assert_eq!(members, vec!["abcdefg", "hijklmn", "opqrstu"]);
+        assert_eq!(c.members_iter().filter(|m| m.starts_with('h')).count(), 1);
+        assert_eq!(c.members_iter().take(2).count(), 2);
+    }
+
+    #[test]
+    fn test_get_n_weighted_frequencies() {
+        let c = Consistent::new().with_number_of_replicas(100);
+        c.add_weighted("w1", 1);
+        c.add_weighted("w2", 2);
+        c.add_weighted("w4", 4);
+        let mut primary = HashMap::<FastStr, usize>::new();
+        let mut chosen = HashMap::<FastStr, usize>::new();
+        let samples = 20000;
+        for i in 0..samples {
+            let replicas = c.get_replicas(format!("user{i}"), 2).unwrap();
+            assert_eq!(replicas.replicas.len(), 1);
+            assert_ne!(replicas.primary, replicas.replicas[0]);
+            *primary.entry(replicas.primary.clone()).or_default() += 1;
+            for m in [replicas.primary, replicas.replicas[0].clone()] {
+                *chosen.entry(m).or_default() += 1;
+            }
+        }
+        // Primaries track the weights 1:2:4.
+        let share = |m: &str| primary[m] as f64 / primary["w1"] as f64;
+        assert!((1.6..2.6).contains(&share("w2")), "{primary:?}");
+        assert!((3.2..5.2).contains(&share("w4")), "{primary:?}");
+        // The pair is drawn as if without replacement: the weighted odds of
+        // being first, or of being second among the members left over.
+        let weights = [("w1", 1.0), ("w2", 2.0), ("w4", 4.0)];
+        for (m, w) in weights {
+            let mut odds = w / 7.0;
+            for (_, wf) in weights.iter().filter(|(f, _)| *f != m) {
+                odds += wf / 7.0 * w / (7.0 - wf);
+            }
+            let observed = chosen[m] as f64 / samples as f64;
+            assert!(
+                (observed / odds - 1.0).abs() < 0.2,
+                "{m}: {observed} vs {odds}"
+            );
+        }
+    }
+
+    // Under `arc-swap` every change copies the layout, so capacity is not kept.
+    #[cfg(not(feature = "arc-swap"))]
+    #[test]
+    fn test_with_bulk() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        c.reserve(100);
+        let capacity = c.sorted_hashes.read().capacity();
+        assert!(capacity >= 2020);
+        let members = (0..50).map(|i| format!("cache{i}")).collect::<Vec<_>>();
+        c.with_bulk(|ring| {
+            for _ in 0..10 {
+                ring.add_many(members.iter().cloned());
+                ring.remove_many(members.iter());
+                assert_eq!(ring.sorted_hashes.read().capacity(), capacity);
+            }
+            ring.with_bulk(|ring| ring.remove_many(members.iter()));
+            assert_eq!(ring.sorted_hashes.read().capacity(), capacity);
+        });
+        assert!(c.sorted_hashes.read().capacity() < capacity);
+        assert_eq!(c.len(), 1);
+        assert_eq!(c.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_eq() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        c.add_weighted("hijklmn", 3);
+        assert_eq!(c, c);
+        let d = Consistent::new();
+        d.add_weighted("hijklmn", 3);
+        d.add("abcdefg");
+        assert_eq!(c, d);
+        let bytes: Consistent = Consistent::from_bytes(&c.to_bytes()).unwrap();
+        assert_eq!(bytes, c);
+
+        d.add_weighted("hijklmn", 2);
+        assert_ne!(c, d);
+        d.add_weighted("hijklmn", 3);
+        assert_eq!(c, d);
+        d.disable("abcdefg");
+        assert_ne!(c, d);
+        d.enable("abcdefg");
+        assert_ne!(c, d.clone().with_hash_seed(1));
+        assert_ne!(c, Consistent::new().with_number_of_replicas(10));
+        assert_ne!(c, Consistent::new());
+
+        // Both weights give 7 replicas at 20, but not at 100.
+        let c = Consistent::new();
+        c.add_weighted_str("abcdefg", "0.33").unwrap();
+        let d = Consistent::new();
+        d.add_weighted_str("abcdefg", "0.35").unwrap();
+        assert_eq!(*c.replicas.get("abcdefg").unwrap(), 7);
+        assert_eq!(*d.replicas.get("abcdefg").unwrap(), 7);
+        assert_ne!(c, d);
+    }
+
+    #[test]
+    fn test_get_with_arc() {
+        let c = Consistent::new();
+        assert!(matches!(
+            c.get_with_arc("user_mcnulty"),
+            Err(Error::EmptyCircle)
+        ));
+        c.add("abcdefg");
+        c.add("hijklmn");
+        for i in 0..100 {
+            let key = format!("user{i}");
+            let (member, distance) = c.get_with_arc(&key).unwrap();
+            let (owner, hash) = c.get_detailed(&key).unwrap();
+            assert_eq!(member, owner);
+            assert_eq!(distance, hash.wrapping_sub(fxhash::hash32(&key)));
+        }
+
+        c.disable("abcdefg");
+        for i in 0..100 {
+            let key = format!("user{i}");
+            let (member, distance) = c.get_with_arc(&key).unwrap();
+            let (owner, hash) = c.get_detailed(&key).unwrap();
+            assert_eq!(member, "hijklmn");
+            assert_eq!(owner, c.get(&key).unwrap());
+            assert_eq!(*c.circle.get(&hash).unwrap(), "hijklmn");
+            assert_eq!(distance, hash.wrapping_sub(fxhash::hash32(&key)));
+        }
+        c.disable("hijklmn");
+        assert!(matches!(c.get_detailed("user1"), Err(Error::EmptyCircle)));
+
+        struct ConstHasher;
+
+        impl Hasher32 for ConstHasher {
+            fn hash32(&self, key: &[u8]) -> u32 {
+                if key == b"0abcdefg" {
+                    10
+                } else {
+                    key.len() as u32
+                }
+            }
+        }
+
+        let c = Consistent::with_hasher(ConstHasher).with_number_of_replicas(1);
+        c.add("abcdefg");
+        assert_eq!(c.get_with_arc("abc").unwrap(), ("abcdefg".into(), 7));
+        assert_eq!(c.get_with_arc("abcdefghijkl").unwrap().1, u32::MAX - 1);
+    }
+
+    #[test]
+    fn test_try_get() {
+        let c = Consistent::new();
+        assert!(matches!(c.try_get("user_mcnulty"), Err(Error::EmptyCircle)));
+        c.add("abcdefg");
+        c.add("hijklmn");
+        assert_eq!(
+            c.try_get("user_mcnulty").unwrap(),
+            Some(c.get("user_mcnulty").unwrap())
+        );
+        let guard = c.sorted_hashes.write();
+        if cfg!(feature = "arc-swap") {
+            assert!(c.try_get("user_mcnulty").unwrap().is_some());
+        } else {
+            assert_eq!(c.try_get("user_mcnulty").unwrap(), None);
+        }
+        drop(guard);
+        assert!(c.try_get("user_mcnulty").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_rebuild() {
+        let c = Consistent::new();
+        assert_eq!(
+            c.rebuild(),
+            RebuildReport {
+                virtual_nodes: 0,
+                collisions: 0,
+                relocations: 0,
+                members: 0,
+            }
+        );
+        c.add("abcdefg");
+        c.add_weighted("hijklmn", 2);
+        let before = c.clone();
+        assert_eq!(
+            c.rebuild(),
+            RebuildReport {
+                virtual_nodes: 60,
+                collisions: 0,
+                relocations: 0,
+                members: 2,
+            }
+        );
+        assert_eq!(c, before);
+
+        struct LenHasher;
+
+        impl Hasher32 for LenHasher {
+            fn hash32(&self, key: &[u8]) -> u32 {
+                key.len() as u32
+            }
+        }
+
+        let c = Consistent::with_hasher(LenHasher)
+            .with_number_of_replicas(1)
+            .with_collision_policy(CollisionPolicy::Overwrite);
+        c.add("b");
+        c.add("a");
+        c.remove("a");
+        assert!(matches!(c.get("x"), Err(Error::EmptyCircle)));
+        let report = c.rebuild();
+        assert_eq!(report.virtual_nodes, 1);
+        assert_eq!(report.collisions, 0);
+        assert_eq!(c.collision_count(), 0);
+        assert_eq!(c.get("x").unwrap(), "b");
+    }
+
+    #[test]
+    fn test_get_or() {
+        let c = Consistent::new();
+        assert_eq!(c.get_or("user_mcnulty", "bootstrap"), "bootstrap");
+        c.add("abcdefg");
+        assert_eq!(c.get_or("user_mcnulty", "bootstrap"), "abcdefg");
+        c.disable("abcdefg");
+        assert_eq!(c.get_or("user_mcnulty", "bootstrap"), "bootstrap");
+    }
+
+    #[test]
+    fn test_balanced_placement() {
+        let cv = |c: &Consistent| {
+            let shares = c.expected_share().into_values().collect::<Vec<_>>();
+            let mean = shares.iter().sum::<f64>() / shares.len() as f64;
+            let var = shares.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / shares.len() as f64;
+            var.sqrt() / mean
+        };
+        let members = (0..50).map(|i| format!("cache{i}")).collect::<Vec<_>>();
+        let plain = Consistent::new();
+        plain.add_many(members.iter().cloned());
+        let balanced = Consistent::new().with_balanced_placement(true);
+        balanced.add_many(members.iter().cloned());
+        assert_eq!(balanced.virtual_node_count(), 1000);
+        let (plain_cv, balanced_cv) = (cv(&plain), cv(&balanced));
+        assert!(balanced_cv < plain_cv * 0.8, "{balanced_cv} vs {plain_cv}");
+
+        let before =
+            balanced.distribution(&(0..1000).map(|i| format!("user{i}")).collect::<Vec<_>>());
+        let estimate = balanced.estimate_remap_fraction_on_add("cache50");
+        balanced.add("cache50");
+        let (_, arc) = balanced
+            .gaps()
+            .into_iter()
+            .find(|(m, _)| m == "cache50")
+            .unwrap();
+        assert!((estimate - arc as f64 / (1u64 << 32) as f64).abs() < 1e-9);
+        balanced.remove("cache50");
+        assert_eq!(balanced.virtual_node_count(), 1000);
+        assert_eq!(balanced.validate(), Ok(()));
+        let after =
+            balanced.distribution(&(0..1000).map(|i| format!("user{i}")).collect::<Vec<_>>());
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn test_members_in_range() {
+        struct NumHasher;
+
+        impl Hasher32 for NumHasher {
+            fn hash32(&self, key: &[u8]) -> u32 {
+                core::str::from_utf8(key).unwrap().parse().unwrap()
+            }
+        }
+
+        let c = Consistent::with_hasher(NumHasher)
+            .with_number_of_replicas(1)
+            .with_replica_key_format(ReplicaKeyFormat::MemberPrefix);
+        assert!(matches!(
+            c.members_in_range("1", "2"),
+            Err(Error::EmptyCircle)
+        ));
+        // Virtual nodes at 100, 200, 300 and 400.
+        for elt in ["10", "20", "30", "40"] {
+            c.add(elt);
+        }
+        let range = |start, end| c.members_in_range(start, end).unwrap();
+        assert_eq!(range("150", "160"), vec!["20"]);
+        assert_eq!(range("150", "250"), vec!["20", "30"]);
+        assert_eq!(range("150", "200"), vec!["20", "30"]);
+        assert_eq!(range("350", "50"), vec!["40", "10"]);
+        assert_eq!(range("450", "50"), vec!["10"]);
+        assert_eq!(range("250", "240"), vec!["30", "40", "10", "20"]);
+
+        c.disable("30");
+        assert_eq!(range("150", "250"), vec!["20", "40"]);
+        assert_eq!(range("210", "220"), vec!["40"]);
+    }
+
+    #[test]
+    fn test_min_replica_spacing() {
+        // Replica `i` hashes to `10 * i`, and each probe moves it a million on.
+        struct CloseHasher;
+
+        impl Hasher32 for CloseHasher {
+            fn hash32(&self, key: &[u8]) -> u32 {
+                let key = core::str::from_utf8(key).unwrap();
+                let (key, probe) = key.split_once('#').unwrap_or((key, "0"));
+                let i = key.trim_end_matches(char::is_alphabetic);
+                i.parse::<u32>().unwrap() * 10 + probe.parse::<u32>().unwrap() * 1_000_000
+            }
+        }
+
+        let c = Consistent::with_hasher(CloseHasher)
+            .with_number_of_replicas(3)
+            .with_min_replica_spacing(100);
+        c.add("abc");
+        let points = c
+            .sorted_hashes
+            .read()
+            .iter()
+            .map(|(h, _)| *h)
+            .collect::<Vec<_>>();
+        assert_eq!(points, vec![0, 1_000_010, 2_000_020]);
+        assert_eq!(c.relocation_count(), 2);
+        assert_eq!(c.validate(), Ok(()));
+        c.set_number_of_replicas(4);
+        assert!(c
+            .sorted_hashes
+            .read()
+            .iter()
+            .any(|(h, _)| *h == 30 + 3_000_000));
+        assert_eq!(c.relocation_count(), 3);
+        c.remove("abc");
+        assert_eq!(c.virtual_node_count(), 0);
+
+        // With every position too close, replicas stay where they hash.
+        let c = Consistent::with_hasher(CloseHasher)
+            .with_number_of_replicas(3)
+            .with_min_replica_spacing(u32::MAX);
+        c.add("abc");
+        let points = c
+            .sorted_hashes
+            .read()
+            .iter()
+            .map(|(h, _)| *h)
+            .collect::<Vec<_>>();
+        assert_eq!(points, vec![0, 10, 20]);
+        assert_eq!(c.relocation_count(), 0);
+    }
+
+    #[test]
+    fn test_get_many() {
+        let c = Consistent::new();
+        assert!(matches!(
+            c.get_many(&["user_mcnulty"]),
+            Err(Error::EmptyCircle)
+        ));
+        for elt in ["abcdefg", "hijklmn", "opqrstu"] {
+            c.add(elt);
+        }
+        assert_eq!(c.get_many(&[] as &[&str]).unwrap(), Vec::<FastStr>::new());
+        c.disable("hijklmn");
+        let keys = (0..100).map(|i| format!("user{i}")).collect::<Vec<_>>();
+        let got = c.get_many(&keys).unwrap();
+        assert_eq!(got.len(), keys.len());
+        for (key, member) in keys.iter().zip(&got) {
+            assert_eq!(c.get(key).unwrap(), *member);
+        }
+    }
+
+    #[test]
+    fn test_get_n_distinct_tags() {
+        let c = Consistent::new();
+        assert!(matches!(
+            c.get_n_distinct_tags("user_mcnulty", 2),
+            Err(Error::EmptyCircle)
+        ));
+        let racks = [
+            ("a1", "rackA"),
+            ("a2", "rackA"),
+            ("a3", "rackA"),
+            ("b1", "rackB"),
+        ];
+        for (elt, rack) in racks {
+            c.add_tagged(elt, rack);
+        }
+        c.add("untagged");
+        assert_eq!(c.tag("a1").unwrap(), "rackA");
+        assert_eq!(c.tag("untagged"), None);
+        let rack = |elt: &FastStr| c.tag(elt);
+        for i in 0..100 {
+            let key = format!("user{i}");
+            let all = c.get_n(&key, 5).unwrap();
+            let got = c.get_n_distinct_tags(&key, 3).unwrap();
+            assert_eq!(got[0], all[0]);
+            let distinct = got.iter().map(rack).collect::<HashSet<_>>();
+            assert_eq!(distinct.len(), 3, "{got:?}");
+
+            // Only rackA is left to fill the last two.
+            let got = c.get_n_distinct_tags(&key, 5).unwrap();
+            assert_eq!(got.iter().collect::<HashSet<_>>(), all.iter().collect());
+            assert_eq!(got[..3].iter().map(rack).collect::<HashSet<_>>().len(), 3);
+        }
+
+        c.add("a1");
+        assert_eq!(c.tag("a1"), None);
+        c.remove("b1");
+        assert_eq!(c.tag("b1"), None);
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        let c = Consistent::new().with_case_insensitive(true);
+        for elt in ["abcdefg", "hijklmn", "opqrstu"] {
+            c.add(elt);
+        }
+        let d = Consistent::new();
+        for elt in ["abcdefg", "hijklmn", "opqrstu"] {
+            d.add(elt);
+        }
+        for i in 0..100 {
+            let key = format!("User{i}Ä");
+            let owner = c.get(&key).unwrap();
+            assert_eq!(owner, d.get(key.to_lowercase()).unwrap());
+            assert_eq!(c.get(key.to_uppercase()).unwrap(), owner);
+            assert_eq!(c.get_bytes(key.as_bytes()).unwrap(), owner);
+            assert_eq!(
+                c.get_n(&key, 2).unwrap(),
+                d.get_n(key.to_lowercase(), 2).unwrap()
+            );
+        }
+        assert_eq!(
+            c.get_bytes(b"USER\xff").unwrap(),
+            c.get_bytes(b"user\xff").unwrap()
+        );
+        assert_eq!(c.snapshot().get("USER7").unwrap(), c.get("user7").unwrap());
+        assert!(!c.contains("ABCDEFG"));
+    }
+
+    #[test]
+    fn test_members_from() {
+        let c = Consistent::new();
+        assert!(matches!(
+            c.members_from("abcdefg"),
+            Err(Error::MemberNotFound(m)) if m == "abcdefg"
+        ));
+        for elt in ["abcdefg", "hijklmn", "opqrstu", "uvwxyz"] {
+            c.add(elt);
+        }
+        c.disable("uvwxyz");
+        let sorted_hashes = c.sorted_hashes.read().clone();
+        for elt in ["abcdefg", "hijklmn", "opqrstu", "uvwxyz"] {
+            let i = sorted_hashes.iter().position(|(_, v)| v == elt).unwrap();
+            let mut expected = Vec::<FastStr>::new();
+            for (_, v) in sorted_hashes[i..].iter().chain(&sorted_hashes[..i]) {
+                if !expected.contains(v) {
+                    expected.push(v.clone());
+                }
+            }
+            let ring = c.members_from(elt).unwrap();
+            assert_eq!(ring[0], elt);
+            assert_eq!(ring, expected);
+        }
+    }
+
+    #[test]
+    fn test_key_namespace() {
+        let members = (0..10).map(|i| format!("cache{i}")).collect::<Vec<_>>();
+        let ring = |ns: &'static str| {
+            let c = Consistent::new().with_key_namespace(ns);
+            c.add_many(members.iter().cloned());
+            c
+        };
+        let plain = ring("");
+        assert_eq!(plain.key_namespace, None);
+        let a = ring("tenantA:");
+        let b = ring("tenantB:");
+        assert_eq!(a.members_iter().count(), 10);
+        assert_eq!(*a.sorted_hashes.read(), *plain.sorted_hashes.read());
+        let keys = (0..100).map(|i| format!("user:{i}")).collect::<Vec<_>>();
+        let differ = keys
+            .iter()
+            .filter(|key| a.get(key).unwrap() != b.get(key).unwrap())
+            .count();
+        assert!(differ > 50, "{differ}");
+        for key in &keys {
+            assert_eq!(
+                a.get(key).unwrap(),
+                plain.get(format!("tenantA:{key}")).unwrap()
+            );
+            assert_eq!(a.snapshot().get(key).unwrap(), a.get(key).unwrap());
+        }
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_compact() {
+        let c = Consistent::new().with_collision_policy(CollisionPolicy::Overwrite);
+        for elt in ["abcdefg", "hijklmn", "opqrstu"] {
+            c.add(elt);
+        }
+        assert_eq!(c.compact(), 0);
+        let before = c.clone();
+
+        // Leave behind a slot of a member that is gone, and a sorted entry
+        // that disagrees with the circle.
+        c.circle.insert(7, "uvwxyz".into());
+        c.sorted_hashes.write().insert(0, (7, "uvwxyz".into()));
+        let (hash, _) = c.sorted_hashes.read()[1].clone();
+        c.sorted_hashes.write()[1].1 = "uvwxyz".into();
+        assert!(c.validate().is_err());
+        assert_eq!(c.compact(), 2);
+        assert_eq!(c.validate(), Ok(()));
+        assert_eq!(c, before);
+        assert!(c.circle.contains_key(&hash));
+        assert_eq!(c.compact(), 0);
+    }
+
+    #[test]
+    fn test_get_n_across_zones() {
+        let c = Consistent::new();
+        assert!(matches!(
+            c.get_n_across_zones("user_mcnulty", 3, 2),
+            Err(Error::EmptyCircle)
+        ));
+        let zones = [("a1", "az1"), ("a2", "az1"), ("a3", "az1"), ("a4", "az1")];
+        for (elt, zone) in zones.into_iter().chain([("b1", "az2"), ("c1", "az3")]) {
+            c.add_tagged(elt, zone);
+        }
+        c.add("untagged");
+        let zone_count = |members: &[FastStr]| {
+            members
+                .iter()
+                .filter_map(|m| c.tag(m))
+                .collect::<HashSet<_>>()
+                .len()
+        };
+        for i in 0..100 {
+            let key = format!("user{i}");
+            let all = c.get_n(&key, 7).unwrap();
+            let (got, met) = c.get_n_across_zones(&key, 4, 3).unwrap();
+            assert!(met);
+            assert_eq!(got.len(), 4);
+            assert_eq!(zone_count(&got), 3, "{got:?}");
+            assert_eq!(got.iter().collect::<HashSet<_>>().len(), 4);
+
+            // Nothing needs skipping when the zones come for free.
+            let (got, met) = c.get_n_across_zones(&key, 4, 1).unwrap();
+            assert!(met);
+            assert_eq!(got, all[..4]);
+
+            let (got, met) = c.get_n_across_zones(&key, 5, 4).unwrap();
+            assert!(!met);
+            assert_eq!(got.len(), 5);
+            assert_eq!(zone_count(&got), 3);
+
+            let (got, met) = c.get_n_across_zones(&key, 2, 3).unwrap();
+            assert!(!met);
+            assert_eq!(zone_count(&got), 2);
+        }
     }
 }
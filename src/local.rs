@@ -0,0 +1,193 @@
+use alloc::{
+    collections::{btree_map::Entry, BTreeMap, BTreeSet},
+    vec::Vec,
+};
+use core::ops::Bound;
+
+use faststr::FastStr;
+
+use crate::{hash_key, Error, FxHasher32, Node, ReplicaKeyFormat, RingHasher, MAX_PROBES};
+
+/// A single-threaded consistent hash ring that only needs `alloc`, for
+/// `no_std` targets.
+///
+/// Virtual nodes are placed exactly as in `Consistent`, so both rings route
+/// every key identically given the same members and configuration. Mutations
+/// take `&mut self` rather than locking.
+#[derive(Debug, Clone)]
+pub struct LocalConsistent<T = FastStr, H: RingHasher = FxHasher32> {
+    circle: BTreeMap<H::Point, T>,
+    members: BTreeMap<FastStr, (T, Vec<H::Point>)>,
+    number_of_replicas: usize,
+    replica_key_format: ReplicaKeyFormat,
+    hash_seed: u64,
+    hasher: H,
+}
+
+impl<T: Node, H: RingHasher + Default> Default for LocalConsistent<T, H> {
+    fn default() -> Self {
+        Self::from_hasher(H::default())
+    }
+}
+
+impl LocalConsistent {
+    pub fn new() -> Self {
+        Self::with_hasher(FxHasher32)
+    }
+}
+
+impl<H: RingHasher> LocalConsistent<FastStr, H> {
+    pub fn with_hasher(hasher: H) -> Self {
+        Self::from_hasher(hasher)
+    }
+}
+
+impl<T: Node, H: RingHasher> LocalConsistent<T, H> {
+    fn from_hasher(hasher: H) -> Self {
+        Self {
+            circle: BTreeMap::new(),
+            members: BTreeMap::new(),
+            number_of_replicas: 20,
+            replica_key_format: ReplicaKeyFormat::default(),
+            hash_seed: 0,
+            hasher,
+        }
+    }
+
+    /// Sets how many virtual nodes each member gets per unit of weight. Zero
+    /// is raised to one, as it would leave members unreachable.
+    pub fn with_number_of_replicas(mut self, number_of_replicas: usize) -> Self {
+        self.number_of_replicas = number_of_replicas.max(1);
+        self
+    }
+
+    pub fn with_replica_key_format(mut self, replica_key_format: ReplicaKeyFormat) -> Self {
+        self.replica_key_format = replica_key_format;
+        self
+    }
+
+    pub fn with_hash_seed(mut self, hash_seed: u64) -> Self {
+        self.hash_seed = hash_seed;
+        self
+    }
+
+    pub fn number_of_replicas(&self) -> usize {
+        self.number_of_replicas
+    }
+
+    pub fn add(&mut self, elt: impl Into<T>) {
+        self.add_weighted(elt, 1);
+    }
+
+    /// Adds `elt` with `number_of_replicas * weight` virtual nodes. As in
+    /// `Consistent`, a weight of zero is raised to one.
+    pub fn add_weighted(&mut self, elt: impl Into<T>, weight: usize) {
+        let elt = elt.into();
+        let key = elt.node_key();
+        self.remove(&key);
+        let replicas = self.number_of_replicas * weight.max(1);
+        let mut hashes = Vec::with_capacity(replicas);
+        for i in 0..replicas {
+            for probe in 0..=MAX_PROBES {
+                let probe_key = self.replica_key_format.probe_key(&key, i, probe);
+                let hash = hash_key(&self.hasher, self.hash_seed, probe_key.as_bytes());
+                if let Entry::Vacant(e) = self.circle.entry(hash) {
+                    e.insert(elt.clone());
+                    hashes.push(hash);
+                    break;
+                }
+            }
+        }
+        self.members.insert(key, (elt, hashes));
+    }
+
+    pub fn remove(&mut self, elt: impl AsRef<str>) {
+        if let Some((_, hashes)) = self.members.remove(elt.as_ref()) {
+            for hash in hashes {
+                self.circle.remove(&hash);
+            }
+        }
+    }
+
+    pub fn contains(&self, elt: impl AsRef<str>) -> bool {
+        self.members.contains_key(elt.as_ref())
+    }
+
+    /// Returns the members ordered by key.
+    pub fn members(&self) -> Vec<T> {
+        self.members.values().map(|(elt, _)| elt.clone()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    pub fn get(&self, name: impl AsRef<str>) -> Result<T, Error> {
+        self.get_bytes(name.as_ref().as_bytes())
+    }
+
+    pub fn get_bytes(&self, name: &[u8]) -> Result<T, Error> {
+        self.walk(name).next().cloned().ok_or(Error::EmptyCircle)
+    }
+
+    pub fn get_n(&self, name: impl AsRef<str>, n: usize) -> Result<Vec<T>, Error> {
+        if self.circle.is_empty() {
+            return Err(Error::EmptyCircle);
+        }
+        let n = n.min(self.len());
+        let mut res = Vec::<T>::with_capacity(n);
+        let mut seen = BTreeSet::new();
+        for v in self.walk(name.as_ref().as_bytes()) {
+            if res.len() == n {
+                break;
+            }
+            if seen.insert(v.node_key()) {
+                res.push(v.clone());
+            }
+        }
+        Ok(res)
+    }
+
+    /// Yields the virtual nodes clockwise from the position of `name`, once
+    /// around the circle.
+    fn walk(&self, name: &[u8]) -> impl Iterator<Item = &T> {
+        let key = hash_key(&self.hasher, self.hash_seed, name);
+        self.circle
+            .range((Bound::Excluded(key), Bound::Unbounded))
+            .chain(self.circle.range(..=key))
+            .map(|(_, v)| v)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::{Consistent, Error, LocalConsistent, ReplicaKeyFormat};
+
+    #[test]
+    fn test_local_matches_consistent() {
+        let mut l = LocalConsistent::new().with_replica_key_format(ReplicaKeyFormat::Ketama);
+        let c = Consistent::new().with_replica_key_format(ReplicaKeyFormat::Ketama);
+        assert!(matches!(l.get("user_mcnulty"), Err(Error::EmptyCircle)));
+        for elt in ["abcdefg", "hijklmn", "opqrstu", "uvwxyz"] {
+            l.add(elt);
+            c.add(elt);
+        }
+        l.remove("hijklmn");
+        c.remove("hijklmn");
+        assert_eq!(l.len(), 3);
+        assert_eq!(l.circle.len(), 60);
+        for i in 0..1000 {
+            let key = format!("user{i}");
+            assert_eq!(l.get(&key).unwrap(), c.get(&key).unwrap());
+            assert_eq!(l.get_n(&key, 2).unwrap(), c.get_n(&key, 2).unwrap());
+        }
+        assert_eq!(l.get_n("user", 5).unwrap().len(), 3);
+
+        l.add_weighted("abcdefg", 0);
+        assert_eq!(l.circle.len(), 60);
+    }
+}
@@ -0,0 +1,55 @@
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+};
+use core::{
+    hash::Hash,
+    net::{IpAddr, SocketAddr},
+};
+
+use faststr::FastStr;
+
+/// A member of the ring.
+///
+/// Members are placed and identified by their [`node_key`](Node::node_key): it
+/// is hashed to place the member's virtual nodes, and it is the name taken by
+/// `remove`, `contains` and friends. Distinct members must have distinct keys.
+pub trait Node: Clone + Eq + Hash {
+    fn node_key(&self) -> FastStr;
+}
+
+impl Node for FastStr {
+    fn node_key(&self) -> FastStr {
+        self.clone()
+    }
+}
+
+impl Node for String {
+    fn node_key(&self) -> FastStr {
+        FastStr::new(self)
+    }
+}
+
+impl Node for Arc<str> {
+    fn node_key(&self) -> FastStr {
+        FastStr::from_arc_str(self.clone())
+    }
+}
+
+impl Node for &'static str {
+    fn node_key(&self) -> FastStr {
+        FastStr::from_static_str(self)
+    }
+}
+
+impl Node for SocketAddr {
+    fn node_key(&self) -> FastStr {
+        self.to_string().into()
+    }
+}
+
+impl Node for IpAddr {
+    fn node_key(&self) -> FastStr {
+        self.to_string().into()
+    }
+}
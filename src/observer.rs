@@ -0,0 +1,41 @@
+use std::{fmt, sync::Arc};
+
+/// Hooks called as the ring changes and routes, e.g. to emit metrics or
+/// tracing spans. Every method defaults to doing nothing.
+///
+/// Hooks run inline, and some while ring locks are held, so they should be
+/// cheap and must not call back into the ring.
+pub trait RingObserver: Send + Sync {
+    /// Called after `member` is put on the ring, including when an existing
+    /// member is re-added with a new weight.
+    fn on_add(&self, member: &str) {
+        let _ = member;
+    }
+
+    /// Called after `member` is taken off the ring.
+    fn on_remove(&self, member: &str) {
+        let _ = member;
+    }
+
+    /// Called when a single-member lookup of `key` picks `chosen`.
+    fn on_get(&self, key: &str, chosen: &str) {
+        let _ = (key, chosen);
+    }
+
+    /// Like [`on_get`](Self::on_get), for a binary key that is not valid
+    /// UTF-8, which only [`get_bytes`](crate::Consistent::get_bytes) can look
+    /// up. Binary keys that are valid UTF-8 route as the same string would,
+    /// and are reported to `on_get`.
+    fn on_get_bytes(&self, key: &[u8], chosen: &str) {
+        let _ = (key, chosen);
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Observer(pub(crate) Arc<dyn RingObserver>);
+
+impl fmt::Debug for Observer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Observer")
+    }
+}
@@ -0,0 +1,90 @@
+use rayon::prelude::*;
+
+use crate::{Consistent, Node, Point, RingHasher};
+
+impl<T: Node + Send + Sync, H: RingHasher + Sync> Consistent<T, H> {
+    /// Like [`add_many`](Self::add_many), but hashes the virtual nodes and
+    /// sorts the circle on the rayon thread pool. Requires the `rayon` feature.
+    ///
+    /// Members are still put on the circle one after another, in iteration
+    /// order, so collisions resolve exactly as with `add_many` and both build
+    /// the same ring. Hashing and sorting, the bulk of the work, run in
+    /// parallel. With balanced placement, which needs the circle so far to
+    /// place each virtual node, or a minimum replica spacing, this is just
+    /// `add_many`.
+    pub fn par_add_many(&self, elts: impl IntoIterator<Item = impl Into<T>>) {
+        if self.balances() || self.min_replica_spacing != H::Point::ZERO {
+            return self.add_many(elts);
+        }
+        let elts = elts.into_iter().map(Into::into).collect::<Vec<T>>();
+        let mut sorted_hashes = self.sorted_hashes.write();
+        let replicas = self.number_of_replicas();
+        let staged = elts
+            .into_par_iter()
+            .map(|elt| {
+                let key = elt.node_key();
+                let hashes = (0..replicas)
+                    .map(|i| {
+                        let probe_key = self.replica_key_format.probe_key(&key, i, 0);
+                        self.hash_key(probe_key.as_bytes())
+                    })
+                    .collect::<Vec<_>>();
+                (elt, key, hashes)
+            })
+            .collect::<Vec<_>>();
+        for (elt, key, hashes) in staged {
+            self.detach(&key);
+            for (i, hash) in hashes.into_iter().enumerate() {
+                self.place_replica(&elt, &key, i, Some(hash), &[]);
+            }
+            self.attach(key, elt, replicas, 1.0);
+        }
+        sorted_hashes.clear();
+        sorted_hashes.extend(self.circle.iter().map(|e| (*e.key(), e.value().to_owned())));
+        sorted_hashes.par_sort_unstable_by_key(|(hash, _)| *hash);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CollisionPolicy, Consistent, Hasher32};
+
+    #[test]
+    fn test_par_add_many() {
+        let members = (0..500).map(|i| format!("cache{i}")).collect::<Vec<_>>();
+        let c = Consistent::new().with_number_of_replicas(40);
+        c.add("cache7");
+        c.par_add_many(members.iter().cloned());
+        let d = Consistent::new().with_number_of_replicas(40);
+        d.add("cache7");
+        d.add_many(members);
+        assert_eq!(c.len(), 500);
+        assert_eq!(*c.sorted_hashes.read(), *d.sorted_hashes.read());
+        assert_eq!(c.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_par_add_many_collisions() {
+        struct ModHasher;
+
+        impl Hasher32 for ModHasher {
+            fn hash32(&self, key: &[u8]) -> u32 {
+                key.iter().map(|b| *b as u32).sum::<u32>() % 64
+            }
+        }
+
+        for policy in [
+            CollisionPolicy::Probe,
+            CollisionPolicy::Overwrite,
+            CollisionPolicy::KeepLowest,
+        ] {
+            let members = (0..20).map(|i| format!("m{i}")).collect::<Vec<_>>();
+            let c = Consistent::with_hasher(ModHasher).with_collision_policy(policy);
+            c.par_add_many(members.iter().cloned());
+            let d = Consistent::with_hasher(ModHasher).with_collision_policy(policy);
+            d.add_many(members);
+            assert_eq!(*c.sorted_hashes.read(), *d.sorted_hashes.read());
+            assert_eq!(c.collision_count(), d.collision_count());
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use faststr::FastStr;
+
+use crate::{Consistent, FxDashMap, Node, RingHasher};
+
+/// How many buckets a rate window is split into. The window slides by one
+/// bucket at a time.
+const BUCKETS: usize = 16;
+
+/// Per-member hit counts over a sliding window, split into buckets that are
+/// reused as the window moves on.
+pub(crate) struct HitRates {
+    window: Duration,
+    start: Instant,
+    members: FxDashMap<FastStr, [AtomicU64; BUCKETS]>,
+}
+
+impl HitRates {
+    fn new(window: Duration) -> Self {
+        Self {
+            // Keep buckets at least a nanosecond wide.
+            window: window.max(Duration::from_nanos(BUCKETS as u64)),
+            start: Instant::now(),
+            members: FxDashMap::default(),
+        }
+    }
+
+    fn bucket_width(&self) -> u128 {
+        self.window.as_nanos() / BUCKETS as u128
+    }
+
+    pub(crate) fn record(&self, member: FastStr) {
+        self.record_at(member, self.start.elapsed());
+    }
+
+    /// Counts a hit `elapsed` after the tracking started. Each bucket packs
+    /// the index of the bucket period it counts for, truncated to 32 bits, in
+    /// its upper half and the count in its lower half, so a stale bucket is
+    /// reset and counted into with one compare-and-swap. The count saturates
+    /// rather than carry into the period.
+    fn record_at(&self, member: FastStr, elapsed: Duration) {
+        let period = (elapsed.as_nanos() / self.bucket_width()) as u64;
+        let tag = period as u32 as u64;
+        let buckets = match self.members.get(&member) {
+            Some(buckets) => buckets,
+            None => self.members.entry(member).or_default().downgrade(),
+        };
+        let _ = buckets[period as usize % BUCKETS].fetch_update(
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+            |bucket| {
+                Some(if bucket >> 32 == tag {
+                    bucket + u64::from(bucket as u32 != u32::MAX)
+                } else {
+                    (tag << 32) | 1
+                })
+            },
+        );
+    }
+
+    pub(crate) fn rates(&self) -> HashMap<FastStr, f64> {
+        self.rates_at(self.start.elapsed())
+    }
+
+    /// Returns each member's hits per second over the window ending
+    /// `elapsed` after the tracking started, which is shorter until a whole
+    /// window has passed.
+    fn rates_at(&self, elapsed: Duration) -> HashMap<FastStr, f64> {
+        let width = self.bucket_width();
+        let current = (elapsed.as_nanos() / width) as u64;
+        let first = current.saturating_sub(BUCKETS as u64 - 1);
+        let span = (elapsed.as_nanos() - first as u128 * width) as f64 / 1e9;
+        self.members
+            .iter()
+            .map(|m| {
+                let hits = m
+                    .value()
+                    .iter()
+                    .map(|bucket| bucket.load(Ordering::Relaxed))
+                    .filter(|bucket| {
+                        let age = (current as u32).wrapping_sub((bucket >> 32) as u32);
+                        age as u64 <= current - first
+                    })
+                    .map(|bucket| bucket & u32::MAX as u64)
+                    .sum::<u64>();
+                let rate = if span > 0.0 { hits as f64 / span } else { 0.0 };
+                (m.key().to_owned(), rate)
+            })
+            .collect()
+    }
+}
+
+impl Clone for HitRates {
+    fn clone(&self) -> Self {
+        Self {
+            window: self.window,
+            start: self.start,
+            members: self
+                .members
+                .iter()
+                .map(|m| {
+                    let buckets = m
+                        .value()
+                        .each_ref()
+                        .map(|bucket| AtomicU64::new(bucket.load(Ordering::Relaxed)));
+                    (m.key().to_owned(), buckets)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl<T: Node, H: RingHasher> Consistent<T, H> {
+    /// Tracks each member's rate of hits by
+    /// [`get_counting`](Self::get_counting) over a sliding `window`, e.g. as
+    /// a signal for autoscaling. Counting a hit is a single atomic update.
+    /// A member's count saturates at `u32::MAX` hits per sixteenth of the
+    /// window. Requires the `hit-rates` feature.
+    pub fn with_rate_window(mut self, window: Duration) -> Self {
+        self.rates = Some(HitRates::new(window));
+        self
+    }
+
+    /// Returns each member's hits per second over the
+    /// [rate window](Self::with_rate_window), keyed by member key. Until a
+    /// whole window has passed, the rate is over the time since the ring was
+    /// built. Members that were never hit are left out.
+    pub fn hit_rates(&self) -> HashMap<FastStr, f64> {
+        self.rates
+            .as_ref()
+            .map_or_else(HashMap::new, HitRates::rates)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::atomic::Ordering, time::Duration};
+
+    use super::HitRates;
+    use crate::Consistent;
+
+    #[test]
+    fn test_hit_rates() {
+        let rates = HitRates::new(Duration::from_secs(16));
+        for _ in 0..10 {
+            rates.record_at("abcdefg".into(), Duration::from_millis(500));
+        }
+        rates.record_at("hijklmn".into(), Duration::from_millis(1500));
+        let r = rates.rates_at(Duration::from_secs(2));
+        assert_eq!(r["abcdefg"], 5.0);
+        assert_eq!(r["hijklmn"], 0.5);
+
+        // A full window later, the early hits have slid out, and the bucket
+        // they were in is reused.
+        for _ in 0..30 {
+            rates.record_at("abcdefg".into(), Duration::from_millis(16_500));
+        }
+        let r = rates.rates_at(Duration::from_secs(20));
+        assert_eq!(r["abcdefg"], 2.0);
+        assert_eq!(r["hijklmn"], 0.0);
+        assert_eq!(rates.clone().rates_at(Duration::from_secs(20)), r);
+    }
+
+    #[test]
+    fn test_hit_rates_saturate() {
+        let rates = HitRates::new(Duration::from_secs(16));
+        rates.record_at("abcdefg".into(), Duration::from_millis(1500));
+        rates.members.get("abcdefg").unwrap()[1]
+            .store((1 << 32) | (u32::MAX as u64 - 1), Ordering::Relaxed);
+        for _ in 0..3 {
+            rates.record_at("abcdefg".into(), Duration::from_millis(1500));
+        }
+        assert_eq!(
+            rates.rates_at(Duration::from_secs(2))["abcdefg"],
+            u32::MAX as f64 / 2.0
+        );
+    }
+
+    #[test]
+    fn test_with_rate_window() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        c.get_counting("user_mcnulty").unwrap();
+        assert!(c.hit_rates().is_empty());
+
+        let c = Consistent::new().with_rate_window(Duration::from_secs(60));
+        c.add("abcdefg");
+        c.add("opqrstu");
+        for i in 0..100 {
+            c.get_counting(format!("user{i}")).unwrap();
+        }
+        let rates = c.hit_rates();
+        assert_eq!(rates.len(), 2);
+        assert!(rates.values().all(|rate| *rate > 0.0));
+        assert!(c.hits().is_empty());
+    }
+}
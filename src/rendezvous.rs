@@ -0,0 +1,168 @@
+use faststr::FastStr;
+
+use crate::{Error, FxDashMap, FxHasher32, Node, RingHasher};
+
+/// Selects members by rendezvous, or highest random weight, hashing: `get`
+/// picks the member whose `hash(key + member)` is the largest.
+///
+/// There are no virtual nodes, so memory is one entry per member and the
+/// balance is as good as the hasher's, but every lookup hashes every member.
+/// It suits small member sets; removing a member only moves the keys it owned.
+#[derive(Debug)]
+pub struct Rendezvous<T = FastStr, H: RingHasher = FxHasher32> {
+    members: FxDashMap<FastStr, T>,
+    hasher: H,
+}
+
+impl<T: Node, H: RingHasher + Default> Default for Rendezvous<T, H> {
+    fn default() -> Self {
+        Self::from_hasher(H::default())
+    }
+}
+
+impl Rendezvous {
+    pub fn new() -> Self {
+        Self::with_hasher(FxHasher32)
+    }
+}
+
+impl<H: RingHasher> Rendezvous<FastStr, H> {
+    pub fn with_hasher(hasher: H) -> Self {
+        Self::from_hasher(hasher)
+    }
+}
+
+impl<T: Node, H: RingHasher> Rendezvous<T, H> {
+    fn from_hasher(hasher: H) -> Self {
+        Self {
+            members: FxDashMap::default(),
+            hasher,
+        }
+    }
+
+    pub fn add(&self, elt: impl Into<T>) {
+        let elt = elt.into();
+        self.members.insert(elt.node_key(), elt);
+    }
+
+    pub fn remove(&self, elt: impl AsRef<str>) {
+        self.members.remove(elt.as_ref());
+    }
+
+    pub fn contains(&self, elt: impl AsRef<str>) -> bool {
+        self.members.contains_key(elt.as_ref())
+    }
+
+    pub fn members(&self) -> Vec<T> {
+        self.members.iter().map(|m| m.value().to_owned()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    pub fn get(&self, name: impl AsRef<str>) -> Result<T, Error> {
+        self.get_bytes(name.as_ref().as_bytes())
+    }
+
+    pub fn get_bytes(&self, name: &[u8]) -> Result<T, Error> {
+        let mut buf = Vec::new();
+        self.members
+            .iter()
+            .max_by_key(|m| (self.score(&mut buf, name, m.key()), m.key().to_owned()))
+            .map(|m| m.value().to_owned())
+            .ok_or(Error::EmptyCircle)
+    }
+
+    /// Returns up to `n` distinct members in descending weight order for
+    /// `name`, the first being what [`get`](Self::get) returns.
+    pub fn get_n(&self, name: impl AsRef<str>, n: usize) -> Result<Vec<T>, Error> {
+        if self.is_empty() {
+            return Err(Error::EmptyCircle);
+        }
+        let name = name.as_ref().as_bytes();
+        let mut buf = Vec::new();
+        let mut scored = self
+            .members
+            .iter()
+            .map(|m| {
+                let score = self.score(&mut buf, name, m.key());
+                (score, m.key().to_owned(), m.value().to_owned())
+            })
+            .collect::<Vec<_>>();
+        scored.sort_unstable_by(|a, b| (&b.0, &b.1).cmp(&(&a.0, &a.1)));
+        Ok(scored.into_iter().take(n).map(|(_, _, v)| v).collect())
+    }
+
+    fn score(&self, buf: &mut Vec<u8>, name: &[u8], member: &str) -> H::Point {
+        buf.clear();
+        buf.extend_from_slice(name);
+        buf.extend_from_slice(member.as_bytes());
+        self.hasher.hash(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{Error, FxHasher32, Rendezvous, RingHasher};
+
+    #[test]
+    fn test_rendezvous_get() {
+        let r = Rendezvous::new();
+        assert!(matches!(r.get("user_mcnulty"), Err(Error::EmptyCircle)));
+        assert!(matches!(
+            r.get_n("user_mcnulty", 2),
+            Err(Error::EmptyCircle)
+        ));
+        for elt in ["abcdefg", "hijklmn", "opqrstu"] {
+            r.add(elt);
+        }
+        assert_eq!(r.len(), 3);
+        let key = "user_mcnulty";
+        let expected = ["abcdefg", "hijklmn", "opqrstu"]
+            .into_iter()
+            .max_by_key(|m| FxHasher32.hash(format!("{key}{m}").as_bytes()))
+            .unwrap();
+        assert_eq!(r.get(key).unwrap(), expected);
+
+        let top = r.get_n(key, 5).unwrap();
+        assert_eq!(top.len(), 3);
+        assert_eq!(top[0], expected);
+        assert_eq!(r.get_n(key, 2).unwrap(), top[..2]);
+    }
+
+    #[test]
+    fn test_rendezvous_remove() {
+        let r = Rendezvous::new();
+        for i in 0..5 {
+            r.add(format!("cache{i}"));
+        }
+        let keys = (0..1000).map(|i| format!("user{i}")).collect::<Vec<_>>();
+        let before = keys
+            .iter()
+            .map(|k| (k, r.get(k).unwrap()))
+            .collect::<HashMap<_, _>>();
+        let mut counts = HashMap::<_, usize>::new();
+        for owner in before.values() {
+            *counts.entry(owner.clone()).or_default() += 1;
+        }
+        assert!(counts.values().all(|&n| n > 100), "{counts:?}");
+
+        r.remove("cache2");
+        assert!(!r.contains("cache2"));
+        for (key, old) in &before {
+            let new = r.get(key).unwrap();
+            if old != "cache2" {
+                assert_eq!(&new, old);
+            } else {
+                assert_ne!(new, "cache2");
+            }
+        }
+    }
+}
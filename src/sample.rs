@@ -0,0 +1,109 @@
+use std::mem::size_of;
+
+use rand::Rng;
+
+use crate::{Consistent, Error, Node, Point, RingHasher};
+
+impl<T: Node, H: RingHasher> Consistent<T, H> {
+    /// Picks a member at random, each with probability equal to its share of
+    /// the keyspace, by routing a uniformly random ring position. Requires
+    /// the `rand` feature.
+    ///
+    /// This is how uniformly spread keys would be shared out, so it suits
+    /// generating realistic synthetic traffic. Disabled members' arcs count
+    /// towards the next enabled member clockwise, as with
+    /// [`get`](Self::get).
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Result<T, Error> {
+        let mut bytes = vec![0; size_of::<H::Point>()];
+        rng.fill_bytes(&mut bytes);
+        let hash = H::Point::read_le(&bytes).expect("read_le decodes a point from as many bytes");
+        self.get_by_hash(hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use crate::{Consistent, Consistent64, Error, Point, RingHasher};
+
+    #[test]
+    fn test_sample() {
+        let mut rng = SmallRng::seed_from_u64(7);
+        let c = Consistent::new();
+        assert!(matches!(c.sample(&mut rng), Err(Error::EmptyCircle)));
+        c.add("abcdefg");
+        c.add_weighted("hijklmn", 3);
+        c.add("opqrstu");
+        let mut counts = HashMap::<_, usize>::new();
+        let samples = 20_000;
+        for _ in 0..samples {
+            *counts.entry(c.sample(&mut rng).unwrap()).or_default() += 1;
+        }
+        for (member, share) in c.expected_share() {
+            let sampled = counts[&member] as f64 / samples as f64;
+            assert!(
+                (sampled - share).abs() < 0.02,
+                "{member}: {sampled} vs {share}"
+            );
+        }
+
+        let c = Consistent64::default();
+        c.add("abcdefg");
+        assert_eq!(c.sample(&mut rng).unwrap(), "abcdefg");
+    }
+
+    #[test]
+    fn test_sample_wide_point() {
+        #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+        struct Wide(u128);
+
+        impl Point for Wide {
+            const ZERO: Self = Wide(0);
+            const MAX: Self = Wide(u128::MAX);
+
+            fn wrapping_sub(self, rhs: Self) -> Self {
+                Wide(self.0.wrapping_sub(rhs.0))
+            }
+
+            fn saturating_add(self, rhs: Self) -> Self {
+                Wide(self.0.saturating_add(rhs.0))
+            }
+
+            fn as_fraction(self) -> f64 {
+                self.0 as f64 / (u128::MAX as f64 + 1.0)
+            }
+
+            fn write_le(self, buf: &mut Vec<u8>) {
+                buf.extend_from_slice(&self.0.to_le_bytes());
+            }
+
+            fn read_le(bytes: &[u8]) -> Option<Self> {
+                Some(Wide(u128::from_le_bytes(*bytes.first_chunk()?)))
+            }
+        }
+
+        struct WideHasher;
+
+        impl RingHasher for WideHasher {
+            type Point = Wide;
+
+            fn hash(&self, key: &[u8]) -> Wide {
+                Wide(
+                    u128::from(fxhash::hash64(key)) << 64
+                        | u128::from(fxhash::hash64(&[key, b"!"].concat())),
+                )
+            }
+        }
+
+        let mut rng = SmallRng::seed_from_u64(7);
+        let c = Consistent::with_hasher(WideHasher);
+        c.add("abcdefg");
+        c.add("hijklmn");
+        for _ in 0..100 {
+            assert!(["abcdefg", "hijklmn"].contains(&c.sample(&mut rng).unwrap().as_str()));
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use core::sync::atomic::Ordering;
+
+use faststr::FastStr;
+
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{CollisionPolicy, Consistent, Node, Point, ReplicaKeyFormat, RingHasher, MAX_REPLICAS};
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+/// The persisted form of a ring. The circle and sorted hashes are determined
+/// by the members and their replica counts, so only those are kept, except
+/// under balanced placement or once virtual nodes have collided, where the
+/// layout also depends on the order the members were added in and the
+/// virtual nodes are kept as well.
+#[derive(Serialize, Deserialize)]
+struct State<T, P> {
+    number_of_replicas: usize,
+    #[serde(default)]
+    replica_key_format: ReplicaKeyFormat,
+    #[serde(default)]
+    collision_policy: CollisionPolicy,
+    #[serde(default)]
+    hash_seed: u64,
+    #[serde(default)]
+    balanced_placement: bool,
+    #[serde(default = "Option::default", skip_serializing_if = "Option::is_none")]
+    min_replica_spacing: Option<P>,
+    #[serde(default)]
+    case_insensitive: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key_namespace: Option<FastStr>,
+    members: Vec<(T, usize)>,
+    /// Each virtual node's position and the index of its member.
+    #[serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")]
+    virtual_nodes: Vec<(P, usize)>,
+    /// The ring's collision count, kept with the virtual nodes so that a
+    /// loaded ring keeps them when it is saved again.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    collisions: usize,
+    /// Each tagged member's key and tag, ordered by key.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<(FastStr, FastStr)>,
+    /// The index and weight of each member whose weight is not its replica
+    /// count over the ring's.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    weights: Vec<(usize, f64)>,
+}
+
+impl<T, H> Serialize for Consistent<T, H>
+where
+    T: Node + Serialize,
+    H: RingHasher,
+    H::Point: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let sorted_hashes = self.sorted_hashes.lock();
+        let mut members = self
+            .members
+            .iter()
+            .map(|m| {
+                let replicas = self.replicas.get(m.key()).map_or(0, |r| *r);
+                (m.key().to_owned(), m.value().to_owned(), replicas)
+            })
+            .collect::<Vec<_>>();
+        members.sort_by(|a, b| a.0.cmp(&b.0));
+        let number_of_replicas = self.number_of_replicas();
+        let weights = members
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (key, _, replicas))| {
+                let weight = *self.weights.get(key)?;
+                (weight != *replicas as f64 / number_of_replicas as f64).then_some((i, weight))
+            })
+            .collect();
+        let collisions = self.collision_count();
+        let virtual_nodes = if self.balanced_placement || collisions > 0 {
+            sorted_hashes
+                .iter()
+                .map(|(hash, member)| {
+                    let key = member.node_key();
+                    (*hash, members.partition_point(|(k, _, _)| *k < key))
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let mut tags = self
+            .tags
+            .iter()
+            .map(|t| (t.key().to_owned(), t.value().to_owned()))
+            .collect::<Vec<_>>();
+        tags.sort();
+        State {
+            number_of_replicas,
+            replica_key_format: self.replica_key_format,
+            collision_policy: self.collision_policy,
+            hash_seed: self.hash_seed,
+            balanced_placement: self.balanced_placement,
+            min_replica_spacing: (self.min_replica_spacing != H::Point::ZERO)
+                .then_some(self.min_replica_spacing),
+            case_insensitive: self.case_insensitive,
+            key_namespace: self.key_namespace.clone(),
+            members: members
+                .into_iter()
+                .map(|(_, elt, replicas)| (elt, replicas))
+                .collect(),
+            virtual_nodes,
+            collisions,
+            tags,
+            weights,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, T, H> Deserialize<'de> for Consistent<T, H>
+where
+    T: Node + Deserialize<'de>,
+    H: RingHasher + Default,
+    H::Point: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let state = State::<T, H::Point>::deserialize(deserializer)?;
+        if state.number_of_replicas > MAX_REPLICAS
+            || state.members.iter().any(|(_, r)| *r > MAX_REPLICAS)
+        {
+            return Err(D::Error::custom("replica count out of range"));
+        }
+        let c = Consistent::default()
+            .with_number_of_replicas(state.number_of_replicas)
+            .with_replica_key_format(state.replica_key_format)
+            .with_collision_policy(state.collision_policy)
+            .with_hash_seed(state.hash_seed)
+            .with_balanced_placement(state.balanced_placement)
+            .with_min_replica_spacing(state.min_replica_spacing.unwrap_or(H::Point::ZERO))
+            .with_case_insensitive(state.case_insensitive)
+            .with_key_namespace(state.key_namespace.unwrap_or_default());
+        {
+            let mut sorted_hashes = c.sorted_hashes.write();
+            let keys = state
+                .members
+                .iter()
+                .map(|(elt, _)| elt.node_key())
+                .collect::<Vec<_>>();
+            if state.virtual_nodes.is_empty() || state.members.is_empty() {
+                for (elt, replicas) in state.members {
+                    c.place(elt, replicas);
+                }
+            } else {
+                for (elt, replicas) in &state.members {
+                    let key = elt.node_key();
+                    c.replicas.insert(key.clone(), *replicas);
+                    c.weights.insert(
+                        key.clone(),
+                        *replicas as f64 / c.number_of_replicas() as f64,
+                    );
+                    if c.members.insert(key, elt.clone()).is_some() {
+                        return Err(D::Error::custom("duplicate member"));
+                    }
+                }
+                c.sorted_hashes.set_members(state.members.len());
+                c.collisions.store(state.collisions, Ordering::Relaxed);
+                for (hash, i) in state.virtual_nodes {
+                    let (elt, _) = state
+                        .members
+                        .get(i)
+                        .ok_or_else(|| D::Error::custom("unknown member index"))?;
+                    c.circle.insert(hash, elt.clone());
+                }
+            }
+            for (elt, tag) in state.tags {
+                if !c.members.contains_key(&elt) {
+                    return Err(D::Error::custom("tag for unknown member"));
+                }
+                c.tags.insert(elt, tag);
+            }
+            for (i, weight) in state.weights {
+                let key = keys
+                    .get(i)
+                    .ok_or_else(|| D::Error::custom("unknown member index"))?;
+                if !(weight.is_finite() && weight > 0.0) {
+                    return Err(D::Error::custom("invalid member weight"));
+                }
+                c.weights.insert(key.clone(), weight);
+            }
+            c.rebuild_sorted_hashes(&mut sorted_hashes);
+        }
+        Ok(c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Consistent, FxHasher32, Hasher32, ReplicaKeyFormat};
+
+    #[test]
+    fn test_serde_round_trip() {
+        let c = Consistent::new()
+            .with_number_of_replicas(10)
+            .with_replica_key_format(ReplicaKeyFormat::Ketama)
+            .with_hash_seed(7);
+        c.add("abcdefg");
+        c.add("opqrstu");
+        c.add_weighted("hijklmn", 3);
+        let json = serde_json::to_string(&c).unwrap();
+        let d: Consistent = serde_json::from_str(&json).unwrap();
+        assert_eq!(d.number_of_replicas(), 10);
+        assert_eq!(d.replica_key_format, ReplicaKeyFormat::Ketama);
+        assert_eq!(d.hash_seed, 7);
+        assert_eq!(d.len(), 3);
+        assert_eq!(*d.sorted_hashes.read(), *c.sorted_hashes.read());
+        for key in ["user_mcnulty", "user_bunk", "user_omar"] {
+            assert_eq!(d.get(key).unwrap(), c.get(key).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_serde_balanced_placement() {
+        let c = Consistent::new().with_balanced_placement(true);
+        for elt in ["opqrstu", "abcdefg", "hijklmn"] {
+            c.add(elt);
+        }
+        let json = serde_json::to_string(&c).unwrap();
+        assert!(json.contains("virtual_nodes"));
+        let d: Consistent = serde_json::from_str(&json).unwrap();
+        assert_eq!(d, c);
+        assert_eq!(d.validate(), Ok(()));
+
+        let plain = serde_json::to_string(&Consistent::new()).unwrap();
+        assert!(!plain.contains("virtual_nodes"));
+    }
+
+    #[test]
+    fn test_serde_collisions() {
+        // Collides every unsalted virtual node of the same length.
+        #[derive(Default)]
+        struct LenHasher;
+
+        impl Hasher32 for LenHasher {
+            fn hash32(&self, key: &[u8]) -> u32 {
+                if key.contains(&b'#') {
+                    FxHasher32.hash32(key)
+                } else {
+                    key.len() as u32
+                }
+            }
+        }
+
+        // "cd" takes the shared positions first, which placing the members
+        // again in key order would hand to "ab".
+        let c = Consistent::with_hasher(LenHasher);
+        c.add("cd");
+        c.add("ab");
+        assert!(c.collision_count() > 0);
+        let json = serde_json::to_string(&c).unwrap();
+        assert!(json.contains("virtual_nodes"));
+        let d: Consistent<_, LenHasher> = serde_json::from_str(&json).unwrap();
+        assert_eq!(d, c);
+        assert_eq!(d.collision_count(), c.collision_count());
+        assert_eq!(serde_json::to_string(&d).unwrap(), json);
+    }
+
+    #[test]
+    fn test_serde_min_replica_spacing() {
+        let c = Consistent::new().with_min_replica_spacing(1 << 24);
+        c.add("abcdefg");
+        c.add("hijklmn");
+        let json = serde_json::to_string(&c).unwrap();
+        let d: Consistent = serde_json::from_str(&json).unwrap();
+        assert_eq!(d, c);
+
+        let plain = serde_json::to_string(&Consistent::new()).unwrap();
+        assert!(!plain.contains("min_replica_spacing"));
+        assert!(!plain.contains("key_namespace"));
+
+        let c = Consistent::new().with_key_namespace("tenantA:");
+        c.add("abcdefg");
+        let d: Consistent = serde_json::from_str(&serde_json::to_string(&c).unwrap()).unwrap();
+        assert_eq!(d, c);
+    }
+
+    #[test]
+    fn test_serde_tags() {
+        let c = Consistent::new();
+        c.add_tagged("abcdefg", "rackA");
+        c.add_tagged("hijklmn", "rackB");
+        c.add("opqrstu");
+        let json = serde_json::to_string(&c).unwrap();
+        let d: Consistent = serde_json::from_str(&json).unwrap();
+        assert_eq!(d, c);
+        assert_eq!(d.tag("hijklmn").unwrap(), "rackB");
+
+        let json = json.replace("\"hijklmn\",\"rackB\"", "\"nobody\",\"rackB\"");
+        assert!(serde_json::from_str::<Consistent>(&json).is_err());
+    }
+
+    #[test]
+    fn test_serde_replica_count_out_of_range() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        let json = serde_json::to_string(&c).unwrap();
+        for corrupted in [
+            json.replace("[\"abcdefg\",20]", &format!("[\"abcdefg\",{}]", u64::MAX)),
+            json.replace(
+                "\"number_of_replicas\":20",
+                "\"number_of_replicas\":4294967296",
+            ),
+        ] {
+            assert_ne!(corrupted, json);
+            let err = serde_json::from_str::<Consistent>(&corrupted).unwrap_err();
+            assert!(err.to_string().contains("replica count out of range"));
+        }
+    }
+
+    #[test]
+    fn test_serde_weights() {
+        let c: Consistent = Consistent::from_replica_counts([("abcdefg", 7), ("opqrstu", 20)]);
+        c.set_number_of_replicas(1);
+        let json = serde_json::to_string(&c).unwrap();
+        assert!(json.contains("weights"));
+        let d: Consistent = serde_json::from_str(&json).unwrap();
+        d.set_number_of_replicas(20);
+        assert_eq!(*d.replicas.get("abcdefg").unwrap(), 7);
+        assert_eq!(*d.replicas.get("opqrstu").unwrap(), 20);
+
+        let plain = serde_json::to_string(&Consistent::new()).unwrap();
+        assert!(!plain.contains("weights"));
+    }
+}
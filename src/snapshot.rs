@@ -0,0 +1,143 @@
+use std::sync::Arc;
+
+use faststr::FastStr;
+use fxhash::FxHashSet;
+
+use crate::{
+    collect_n, hash_key, lookup_bytes, search_sorted, Consistent, Error, FxHasher32, Node,
+    RingHasher,
+};
+
+/// An immutable copy of a ring's layout, taken by
+/// [`Consistent::snapshot`], that answers lookups without any locking.
+///
+/// The snapshot does not follow later changes to the ring, so it may route to
+/// members that have since been removed or disabled. Cloning it is cheap and
+/// shares the layout.
+#[derive(Debug, Clone)]
+pub struct RingSnapshot<T = FastStr, H: RingHasher = FxHasher32> {
+    inner: Arc<Inner<T, H>>,
+}
+
+#[derive(Debug)]
+struct Inner<T, H: RingHasher> {
+    sorted_hashes: Vec<(H::Point, T)>,
+    disabled: FxHashSet<FastStr>,
+    members: usize,
+    case_insensitive: bool,
+    key_namespace: Option<FastStr>,
+    hash_seed: u64,
+    hasher: H,
+}
+
+impl<T: Node, H: RingHasher + Clone> Consistent<T, H> {
+    /// Copies the current layout into a [`RingSnapshot`] for a burst of
+    /// lock-free lookups.
+    pub fn snapshot(&self) -> RingSnapshot<T, H> {
+        let sorted_hashes = self.sorted_hashes.lock();
+        RingSnapshot {
+            inner: Arc::new(Inner {
+                sorted_hashes: sorted_hashes.clone(),
+                disabled: self.disabled.iter().map(|d| d.key().to_owned()).collect(),
+                members: sorted_hashes.members(),
+                case_insensitive: self.case_insensitive,
+                key_namespace: self.key_namespace.clone(),
+                hash_seed: self.hash_seed,
+                hasher: self.hasher.clone(),
+            }),
+        }
+    }
+}
+
+impl<T: Node, H: RingHasher> RingSnapshot<T, H> {
+    pub fn len(&self) -> usize {
+        self.inner.members
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.members == 0
+    }
+
+    /// Returns the member that owned `name` when the snapshot was taken.
+    pub fn get(&self, name: impl AsRef<str>) -> Result<T, Error> {
+        self.get_bytes(name.as_ref().as_bytes())
+    }
+
+    pub fn get_bytes(&self, name: &[u8]) -> Result<T, Error> {
+        self.walk(name)
+            .find(|v| self.is_enabled(v))
+            .cloned()
+            .ok_or(Error::EmptyCircle)
+    }
+
+    /// Returns up to `n` distinct members in ring-walk order, as
+    /// [`Consistent::get_n`] did when the snapshot was taken.
+    pub fn get_n(&self, name: impl AsRef<str>, n: usize) -> Result<Vec<T>, Error> {
+        let count = self.inner.members.saturating_sub(self.inner.disabled.len());
+        if self.inner.sorted_hashes.is_empty() || count == 0 {
+            return Err(Error::EmptyCircle);
+        }
+        Ok(collect_n(
+            &self.inner.sorted_hashes,
+            self.lookup_key(name.as_ref().as_bytes()),
+            self.inner.members,
+            n.min(count),
+            |v| self.is_enabled(v),
+        ))
+    }
+
+    /// Yields the virtual nodes clockwise from the position of `name`, once
+    /// around the circle.
+    fn walk(&self, name: &[u8]) -> impl Iterator<Item = &T> {
+        let sorted_hashes = &self.inner.sorted_hashes;
+        let i = search_sorted(sorted_hashes, self.lookup_key(name));
+        sorted_hashes[i..]
+            .iter()
+            .chain(&sorted_hashes[..i])
+            .map(|(_, v)| v)
+    }
+
+    fn lookup_key(&self, name: &[u8]) -> H::Point {
+        let inner = &self.inner;
+        let name = lookup_bytes(name, inner.case_insensitive, inner.key_namespace.as_deref());
+        hash_key(&inner.hasher, inner.hash_seed, &name)
+    }
+
+    fn is_enabled(&self, elt: &T) -> bool {
+        self.inner.disabled.is_empty() || !self.inner.disabled.contains(&elt.node_key())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use crate::{Consistent, Error};
+
+    #[test]
+    fn test_snapshot() {
+        let c = Consistent::new();
+        assert!(matches!(
+            c.snapshot().get("user_mcnulty"),
+            Err(Error::EmptyCircle)
+        ));
+        for elt in ["abcdefg", "hijklmn", "opqrstu", "uvwxyz"] {
+            c.add(elt);
+        }
+        c.disable("uvwxyz");
+        let snapshot = c.snapshot();
+        assert_eq!(snapshot.len(), 4);
+        for i in 0..200 {
+            let key = format!("user{i}");
+            assert_eq!(snapshot.get(&key).unwrap(), c.get(&key).unwrap());
+            assert_eq!(snapshot.get_n(&key, 5).unwrap(), c.get_n(&key, 5).unwrap());
+        }
+
+        let owner = c.get("user_mcnulty").unwrap();
+        c.remove(&owner);
+        assert_eq!(snapshot.get("user_mcnulty").unwrap(), owner);
+        let shared = snapshot.clone();
+        let got = thread::spawn(move || shared.get("user_mcnulty").unwrap());
+        assert_eq!(got.join().unwrap(), owner);
+    }
+}
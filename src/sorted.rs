@@ -0,0 +1,277 @@
+// Storage for the sorted hashes of a `Consistent`, along with its member
+// count, so that lookups see a count that agrees with the layout they walk.
+//
+// By default they sit behind an `RwLock`. With the `arc-swap` feature they
+// are published through an `ArcSwap` instead: readers load the current
+// layout without ever blocking, and writers, serialized by a mutex, copy it
+// on their first change and publish the copy, with the member count as it
+// then stands, when done.
+//
+// Either way, `read` is for lookups that only need the sorted hashes, while
+// `lock` also keeps writers out, for readers that look at the rest of the
+// ring too and need it to agree with the sorted hashes.
+
+use std::{fmt, ops::Deref};
+
+#[cfg(not(feature = "arc-swap"))]
+mod imp {
+    use std::{
+        ops::Deref,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+    pub(crate) struct SortedHashes<E> {
+        entries: RwLock<Vec<E>>,
+        members: AtomicUsize,
+    }
+
+    impl<E> SortedHashes<E> {
+        pub(crate) fn new(entries: Vec<E>, members: usize) -> Self {
+            Self {
+                entries: RwLock::new(entries),
+                members: AtomicUsize::new(members),
+            }
+        }
+
+        pub(crate) fn read(&self) -> ReadGuard<'_, E> {
+            let entries = self.entries.read();
+            ReadGuard {
+                members: self.members(),
+                entries,
+            }
+        }
+
+        /// Like `read`, but returns `None` instead of blocking on a writer.
+        pub(crate) fn try_read(&self) -> Option<ReadGuard<'_, E>> {
+            let entries = self.entries.try_read()?;
+            Some(ReadGuard {
+                members: self.members(),
+                entries,
+            })
+        }
+
+        pub(crate) fn lock(&self) -> ReadGuard<'_, E> {
+            self.read()
+        }
+
+        pub(crate) fn write(&self) -> RwLockWriteGuard<'_, Vec<E>> {
+            self.entries.write()
+        }
+
+        pub(crate) fn members(&self) -> usize {
+            self.members.load(Ordering::Relaxed)
+        }
+
+        /// Updates the member count. Only called by writers.
+        pub(crate) fn set_members(&self, members: usize) {
+            self.members.store(members, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) struct ReadGuard<'a, E> {
+        entries: RwLockReadGuard<'a, Vec<E>>,
+        members: usize,
+    }
+
+    impl<E> ReadGuard<'_, E> {
+        pub(crate) fn members(&self) -> usize {
+            self.members
+        }
+    }
+
+    impl<E> Deref for ReadGuard<'_, E> {
+        type Target = Vec<E>;
+
+        fn deref(&self) -> &Vec<E> {
+            &self.entries
+        }
+    }
+}
+
+#[cfg(feature = "arc-swap")]
+mod imp {
+    use std::{
+        marker::PhantomData,
+        ops::{Deref, DerefMut},
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use arc_swap::{ArcSwap, Guard};
+    use parking_lot::{Mutex, MutexGuard};
+
+    struct Layout<E> {
+        entries: Arc<Vec<E>>,
+        members: usize,
+    }
+
+    pub(crate) struct SortedHashes<E> {
+        current: ArcSwap<Layout<E>>,
+        writer: Mutex<()>,
+        /// The member count writers are working towards, published with the
+        /// layout.
+        members: AtomicUsize,
+    }
+
+    impl<E> SortedHashes<E> {
+        pub(crate) fn new(entries: Vec<E>, members: usize) -> Self {
+            Self {
+                current: ArcSwap::from_pointee(Layout {
+                    entries: Arc::new(entries),
+                    members,
+                }),
+                writer: Mutex::new(()),
+                members: AtomicUsize::new(members),
+            }
+        }
+
+        pub(crate) fn read(&self) -> ReadGuard<'_, E> {
+            ReadGuard(self.current.load(), PhantomData)
+        }
+
+        /// Never blocks, so always succeeds.
+        pub(crate) fn try_read(&self) -> Option<ReadGuard<'_, E>> {
+            Some(self.read())
+        }
+
+        pub(crate) fn lock(&self) -> LockGuard<'_, E> {
+            let writer = self.writer.lock();
+            LockGuard {
+                _writer: writer,
+                current: self.current.load_full(),
+            }
+        }
+
+        pub(crate) fn write(&self) -> WriteGuard<'_, E> {
+            let writer = self.writer.lock();
+            WriteGuard {
+                _writer: writer,
+                shared: self,
+                current: self.current.load_full(),
+                changed: None,
+            }
+        }
+
+        /// Returns the published member count.
+        pub(crate) fn members(&self) -> usize {
+            self.current.load().members
+        }
+
+        /// Updates the member count, to be published by the current writer.
+        /// Only called by writers.
+        pub(crate) fn set_members(&self, members: usize) {
+            self.members.store(members, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) struct ReadGuard<'a, E>(Guard<Arc<Layout<E>>>, PhantomData<&'a ()>);
+
+    impl<E> ReadGuard<'_, E> {
+        pub(crate) fn members(&self) -> usize {
+            self.0.members
+        }
+    }
+
+    impl<E> Deref for ReadGuard<'_, E> {
+        type Target = Vec<E>;
+
+        fn deref(&self) -> &Vec<E> {
+            &self.0.entries
+        }
+    }
+
+    pub(crate) struct LockGuard<'a, E> {
+        _writer: MutexGuard<'a, ()>,
+        current: Arc<Layout<E>>,
+    }
+
+    impl<E> LockGuard<'_, E> {
+        pub(crate) fn members(&self) -> usize {
+            self.current.members
+        }
+    }
+
+    impl<E> Deref for LockGuard<'_, E> {
+        type Target = Vec<E>;
+
+        fn deref(&self) -> &Vec<E> {
+            &self.current.entries
+        }
+    }
+
+    /// Copies the sorted hashes on the first mutable access and publishes
+    /// them with the member count when dropped, so a guard that changes
+    /// neither costs nothing.
+    pub(crate) struct WriteGuard<'a, E> {
+        _writer: MutexGuard<'a, ()>,
+        shared: &'a SortedHashes<E>,
+        current: Arc<Layout<E>>,
+        changed: Option<Vec<E>>,
+    }
+
+    impl<E> Deref for WriteGuard<'_, E> {
+        type Target = Vec<E>;
+
+        fn deref(&self) -> &Vec<E> {
+            self.changed.as_ref().unwrap_or(&self.current.entries)
+        }
+    }
+
+    impl<E: Clone> DerefMut for WriteGuard<'_, E> {
+        fn deref_mut(&mut self) -> &mut Vec<E> {
+            let current = &self.current;
+            self.changed.get_or_insert_with(|| current.entries.to_vec())
+        }
+    }
+
+    impl<E> Drop for WriteGuard<'_, E> {
+        fn drop(&mut self) {
+            let members = self.shared.members.load(Ordering::Relaxed);
+            if self.changed.is_none() && members == self.current.members {
+                return;
+            }
+            let entries = match self.changed.take() {
+                Some(changed) => Arc::new(changed),
+                None => self.current.entries.clone(),
+            };
+            self.shared
+                .current
+                .store(Arc::new(Layout { entries, members }));
+        }
+    }
+}
+
+pub(crate) use imp::SortedHashes;
+
+/// A member borrowed from the ring by
+/// [`Consistent::get_ref`](crate::Consistent::get_ref), read through
+/// `Deref`. It keeps the sorted hashes it was found in alive, see
+/// `get_ref` for what that means for writers.
+pub struct MemberRef<'a, T, P> {
+    guard: imp::ReadGuard<'a, (P, T)>,
+    index: usize,
+}
+
+impl<'a, T, P> MemberRef<'a, T, P> {
+    pub(crate) fn new(guard: imp::ReadGuard<'a, (P, T)>, index: usize) -> Self {
+        Self { guard, index }
+    }
+}
+
+impl<T, P> Deref for MemberRef<'_, T, P> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard[self.index].1
+    }
+}
+
+impl<T: fmt::Debug, P> fmt::Debug for MemberRef<'_, T, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
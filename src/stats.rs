@@ -0,0 +1,414 @@
+use std::collections::HashMap;
+
+use faststr::FastStr;
+
+use crate::{Consistent, Node, Point, RingHasher};
+
+impl<T: Node, H: RingHasher> Consistent<T, H> {
+    /// Routes every sample key and tallies how many landed on each member.
+    /// Members that received no key are reported with a count of zero.
+    pub fn distribution(&self, sample_keys: &[impl AsRef<str>]) -> HashMap<T, usize> {
+        let mut res = self
+            .members
+            .iter()
+            .map(|member| (member.value().to_owned(), 0))
+            .collect::<HashMap<_, _>>();
+        for key in sample_keys {
+            if let Ok(member) = self.get(key) {
+                *res.entry(member).or_default() += 1;
+            }
+        }
+        res
+    }
+
+    /// Returns each member's total arc length on the circle, i.e. the size of
+    /// the part of the keyspace routed to it, ordered by member key.
+    ///
+    /// A member owning the whole circle saturates at `Point::MAX`.
+    pub fn gaps(&self) -> Vec<(T, H::Point)> {
+        let mut gaps = HashMap::<T, H::Point>::new();
+        for (member, arc) in self.arcs() {
+            let total = gaps.entry(member).or_insert(H::Point::ZERO);
+            *total = total.saturating_add(arc);
+        }
+        let mut gaps = gaps.into_iter().collect::<Vec<_>>();
+        gaps.sort_by_cached_key(|(member, _)| member.node_key());
+        gaps
+    }
+
+    /// Returns each member's fraction of the whole circle, i.e. the share of
+    /// the keyspace routed to it. With [`add_weighted`](Self::add_weighted),
+    /// shares approach `weight / total_weight` as replicas grow.
+    pub fn expected_share(&self) -> HashMap<T, f64> {
+        let mut shares = HashMap::new();
+        for (member, arc) in self.arcs() {
+            *shares.entry(member).or_default() += arc.as_fraction();
+        }
+        shares
+    }
+
+    /// Returns the largest and smallest gap between consecutive virtual nodes,
+    /// wrapping around the ring, or `None` if the circle is empty. A largest
+    /// gap far above the average, `Point::MAX` over the number of virtual
+    /// nodes, points at a member holding much more of the keyspace than the
+    /// others. A single virtual node spans the whole circle, saturating at
+    /// `Point::MAX`.
+    pub fn arc_extremes(&self) -> Option<(H::Point, H::Point)> {
+        let sorted_hashes = self.sorted_hashes.read();
+        let (last, _) = sorted_hashes.last()?;
+        if sorted_hashes.len() == 1 {
+            return Some((H::Point::MAX, H::Point::MAX));
+        }
+        let mut prev = *last;
+        let mut extremes = (H::Point::ZERO, H::Point::MAX);
+        for (hash, _) in sorted_hashes.iter() {
+            let arc = hash.wrapping_sub(prev);
+            extremes = (extremes.0.max(arc), extremes.1.min(arc));
+            prev = *hash;
+        }
+        Some(extremes)
+    }
+
+    /// Returns every virtual node as its ring position paired with the member it
+    /// maps to, in ascending position order. This is a consistent snapshot of
+    /// the ring layout, taken under the read lock.
+    pub fn ring_entries(&self) -> Vec<(H::Point, T)> {
+        let sorted_hashes = self.sorted_hashes.read();
+        sorted_hashes.clone()
+    }
+
+    /// Returns the ring positions of `elt`'s virtual nodes in ascending order,
+    /// or an empty vec if `elt` is not a member.
+    pub fn virtual_nodes(&self, elt: impl AsRef<str>) -> Vec<H::Point> {
+        let elt = elt.as_ref();
+        let sorted_hashes = self.sorted_hashes.read();
+        if !self.contains(elt) {
+            return Vec::new();
+        }
+        sorted_hashes
+            .iter()
+            .filter(|(_, member)| member.node_key() == elt)
+            .map(|(hash, _)| *hash)
+            .collect()
+    }
+
+    /// Returns the positions `elt`'s virtual nodes hash to, in replica index
+    /// order, computed from the replica key format and hash seed alone: the
+    /// circle is not consulted, so `elt` need not be a member, and collisions
+    /// and the placement policy are not applied. Useful for checking the
+    /// ring against another implementation.
+    pub fn replica_positions(&self, elt: impl AsRef<str>) -> Vec<H::Point> {
+        let elt = elt.as_ref();
+        (0..self.number_of_replicas())
+            .map(|i| self.hash_key(self.replica_key_format.elt_key(elt, i).as_bytes()))
+            .collect()
+    }
+
+    /// Returns `(key, old_owner, new_owner)` for every sample key whose owner
+    /// would change if `new_nodes` were added, simulated on a clone of the ring
+    /// without mutating it.
+    pub fn remap_plan(
+        &self,
+        new_nodes: impl IntoIterator<Item = impl Into<T>>,
+        sample_keys: &[impl AsRef<str>],
+    ) -> Vec<(FastStr, T, T)>
+    where
+        H: Clone,
+    {
+        let next = self.clone();
+        next.add_many(new_nodes);
+        sample_keys
+            .iter()
+            .filter_map(|key| {
+                let old = self.get(key).ok()?;
+                let new = next.get(key).ok()?;
+                (old != new).then(|| (FastStr::new(key.as_ref()), old, new))
+            })
+            .collect()
+    }
+
+    /// Estimates the fraction of the keyspace that would change owner if `elt`
+    /// were added with [`add`](Self::add), without mutating the ring.
+    pub fn estimate_remap_fraction_on_add(&self, elt: impl AsRef<str>) -> f64 {
+        let elt = elt.as_ref();
+        if self.contains(elt) {
+            return 0.0;
+        }
+        let sorted_hashes = self.sorted_hashes.lock();
+        let replicas = self.number_of_replicas();
+        // Placed as `add` would, but with the new virtual nodes kept off the
+        // circle, so they count as held by `elt` only here.
+        let mut positions = self.plan_replicas(
+            elt,
+            0..replicas,
+            Vec::with_capacity(replicas),
+            |hash, siblings| {
+                self.occupant(hash)
+                    .or_else(|| siblings.contains(&hash).then(|| FastStr::new(elt)))
+            },
+            |_| {},
+        );
+        if positions.is_empty() {
+            return 0.0;
+        }
+        if sorted_hashes.is_empty() {
+            return 1.0;
+        }
+        positions.sort();
+        positions.dedup();
+        let mut fraction = 0.0;
+        for (k, &hash) in positions.iter().enumerate() {
+            // The new virtual node takes the arc back to its closest predecessor,
+            // whether that is an existing virtual node or one of its siblings.
+            let i = sorted_hashes.partition_point(|(x, _)| *x < hash);
+            let prev = sorted_hashes[i.checked_sub(1).unwrap_or(sorted_hashes.len() - 1)].0;
+            let mut arc = hash.wrapping_sub(prev);
+            if positions.len() > 1 {
+                let prev = positions[k.checked_sub(1).unwrap_or(positions.len() - 1)];
+                arc = arc.min(hash.wrapping_sub(prev));
+            }
+            fraction += arc.as_fraction();
+        }
+        fraction
+    }
+
+    /// Estimates the fraction of the keyspace that would change owner if `elt`
+    /// were removed, without mutating the ring.
+    pub fn estimate_remap_fraction_on_remove(&self, elt: impl AsRef<str>) -> f64 {
+        let Some(node) = self.members.get(elt.as_ref()).map(|m| m.value().to_owned()) else {
+            return 0.0;
+        };
+        if self.len() == 1 {
+            return 1.0;
+        }
+        self.arcs()
+            .into_iter()
+            .filter(|(member, _)| *member == node)
+            .map(|(_, arc)| arc.as_fraction())
+            .sum()
+    }
+
+    /// Returns the owner and length of every arc, in ring order. The virtual
+    /// node at `sorted_hashes[j]` owns the keys from `sorted_hashes[j - 1]` up
+    /// to, but excluding, its own hash.
+    fn arcs(&self) -> Vec<(T, H::Point)> {
+        let sorted_hashes = self.sorted_hashes.read();
+        let mut arcs = Vec::with_capacity(sorted_hashes.len());
+        if let [(_, member)] = &sorted_hashes[..] {
+            arcs.push((member.to_owned(), H::Point::MAX));
+            return arcs;
+        }
+        for (j, (hash, member)) in sorted_hashes.iter().enumerate() {
+            let prev = sorted_hashes[j.checked_sub(1).unwrap_or(sorted_hashes.len() - 1)].0;
+            arcs.push((member.to_owned(), hash.wrapping_sub(prev)));
+        }
+        arcs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CollisionPolicy, Consistent, FxHasher32, Hasher32, ReplicaKeyFormat};
+
+    #[test]
+    fn test_distribution() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        c.add("opqrstu");
+        c.add("hijklmn");
+        let keys = (0..1000).map(|i| format!("user{i}")).collect::<Vec<_>>();
+        let res = c.distribution(&keys);
+        assert_eq!(res.len(), 3);
+        assert_eq!(res.values().sum::<usize>(), 1000);
+        assert_eq!(
+            res["abcdefg"],
+            keys.iter()
+                .filter(|k| c.get(k).unwrap() == "abcdefg")
+                .count()
+        );
+    }
+
+    #[test]
+    fn test_gaps() {
+        let c = Consistent::new();
+        assert!(c.gaps().is_empty());
+        c.add("abcdefg");
+        assert_eq!(c.gaps(), vec![("abcdefg".into(), u32::MAX)]);
+        c.add("opqrstu");
+        c.add("hijklmn");
+        let gaps = c.gaps();
+        assert_eq!(
+            gaps.iter().map(|(m, _)| m.as_str()).collect::<Vec<_>>(),
+            vec!["abcdefg", "hijklmn", "opqrstu"]
+        );
+        let total = gaps.iter().map(|(_, arc)| *arc as u64).sum::<u64>();
+        assert_eq!(total, 1 << 32);
+    }
+
+    #[test]
+    fn test_estimate_remap_fraction() {
+        let c = Consistent::new();
+        assert_eq!(c.estimate_remap_fraction_on_add("abcdefg"), 1.0);
+        c.add("abcdefg");
+        c.add("opqrstu");
+        c.add("hijklmn");
+        assert_eq!(c.estimate_remap_fraction_on_add("abcdefg"), 0.0);
+        assert_eq!(c.estimate_remap_fraction_on_remove("qwer"), 0.0);
+
+        let estimate = c.estimate_remap_fraction_on_add("qwer");
+        let d = c.clone();
+        d.add("qwer");
+        let (_, arc) = d.gaps().into_iter().find(|(m, _)| m == "qwer").unwrap();
+        assert!((estimate - arc as f64 / (1u64 << 32) as f64).abs() < 1e-9);
+        assert!((d.estimate_remap_fraction_on_remove("qwer") - estimate).abs() < 1e-9);
+        assert!(estimate > 0.0 && estimate < 1.0);
+
+        let total = ["abcdefg", "opqrstu", "hijklmn"]
+            .iter()
+            .map(|m| c.estimate_remap_fraction_on_remove(m))
+            .sum::<f64>();
+        assert!((total - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_estimate_remap_fraction_placement_rules() {
+        // Few enough positions that virtual nodes collide.
+        struct ModHasher;
+
+        impl Hasher32 for ModHasher {
+            fn hash32(&self, key: &[u8]) -> u32 {
+                FxHasher32.hash32(key) % 4096 * (1 << 20)
+            }
+        }
+
+        for policy in [
+            CollisionPolicy::Probe,
+            CollisionPolicy::Overwrite,
+            CollisionPolicy::KeepLowest,
+        ] {
+            for (balanced, spacing) in [(false, 0), (false, 1 << 26), (true, 0), (true, 1 << 26)] {
+                let c = Consistent::with_hasher(ModHasher)
+                    .with_number_of_replicas(40)
+                    .with_collision_policy(policy)
+                    .with_balanced_placement(balanced)
+                    .with_min_replica_spacing(spacing);
+                c.add_many((0..40).map(|i| format!("cache{i}")));
+                assert!(c.collision_count() > 0);
+                let estimate = c.estimate_remap_fraction_on_add("cache40");
+                c.add("cache40");
+                let (_, arc) = c.gaps().into_iter().find(|(m, _)| m == "cache40").unwrap();
+                assert!(
+                    (estimate - arc as f64 / (1u64 << 32) as f64).abs() < 1e-9,
+                    "{policy:?}, balanced {balanced}, spacing {spacing}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_ring_entries() {
+        let c = Consistent::new();
+        assert!(c.ring_entries().is_empty());
+        c.add("abcdefg");
+        c.add("opqrstu");
+        let entries = c.ring_entries();
+        assert_eq!(entries.len(), 40);
+        assert!(entries.windows(2).all(|w| w[0].0 < w[1].0));
+        for (hash, member) in &entries {
+            assert_eq!(*c.circle.get(hash).unwrap(), *member);
+        }
+        assert_eq!(entries.iter().filter(|(_, m)| m == "abcdefg").count(), 20);
+    }
+
+    #[test]
+    fn test_expected_share() {
+        let c = Consistent::new().with_number_of_replicas(200);
+        assert!(c.expected_share().is_empty());
+        c.add("solo");
+        assert!((c.expected_share()["solo"] - 1.0).abs() < 1e-6);
+        c.remove("solo");
+
+        c.add_weighted("heavy", 3);
+        c.add("light");
+        let shares = c.expected_share();
+        assert!((shares.values().sum::<f64>() - 1.0).abs() < 1e-6);
+        let ratio = shares["heavy"] / shares["light"];
+        assert!(ratio > 2.0 && ratio < 4.5, "ratio {ratio}");
+    }
+
+    #[test]
+    fn test_arc_extremes() {
+        let c = Consistent::new();
+        assert_eq!(c.arc_extremes(), None);
+        c.add_many(["abcdefg", "hijklmn", "opqrstu"]);
+        let arcs = c.arcs().into_iter().map(|(_, arc)| arc).collect::<Vec<_>>();
+        assert_eq!(
+            c.arc_extremes(),
+            Some((*arcs.iter().max().unwrap(), *arcs.iter().min().unwrap()))
+        );
+
+        let c = Consistent::new().with_number_of_replicas(1);
+        c.add("abcdefg");
+        assert_eq!(c.arc_extremes(), Some((u32::MAX, u32::MAX)));
+    }
+
+    #[test]
+    fn test_virtual_nodes() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        c.add_weighted("opqrstu", 2);
+        assert!(c.virtual_nodes("hijklmn").is_empty());
+        let vnodes = c.virtual_nodes("opqrstu");
+        assert_eq!(vnodes.len(), 40);
+        assert!(vnodes.windows(2).all(|w| w[0] < w[1]));
+        for hash in vnodes {
+            assert_eq!(*c.circle.get(&hash).unwrap(), "opqrstu");
+        }
+        assert_eq!(c.virtual_nodes("abcdefg").len(), 20);
+    }
+
+    #[test]
+    fn test_replica_positions() {
+        let c = Consistent::new();
+        let positions = c.replica_positions("abcdefg");
+        assert_eq!(positions.len(), 20);
+        for (i, hash) in positions.iter().enumerate() {
+            assert_eq!(*hash, fxhash::hash32(&format!("{i}abcdefg")));
+        }
+        c.add("abcdefg");
+        let mut sorted = positions.clone();
+        sorted.sort_unstable();
+        assert_eq!(c.virtual_nodes("abcdefg"), sorted);
+
+        let c = Consistent::new()
+            .with_replica_key_format(ReplicaKeyFormat::Ketama)
+            .with_hash_seed(7);
+        assert_eq!(
+            c.replica_positions("abcdefg")[1],
+            fxhash::hash32("7:abcdefg-1")
+        );
+    }
+
+    #[test]
+    fn test_remap_plan() {
+        let c = Consistent::new();
+        c.add("abcdefg");
+        c.add("hijklmn");
+        let keys = (0..1000).map(|i| format!("user{i}")).collect::<Vec<_>>();
+        let plan = c.remap_plan(["opqrstu", "uvwxyz"], &keys);
+        assert_eq!(c.len(), 2);
+        assert!(!plan.is_empty() && plan.len() < keys.len());
+
+        let next = c.clone();
+        next.add("opqrstu");
+        next.add("uvwxyz");
+        for key in &keys {
+            let (old, new) = (c.get(key).unwrap(), next.get(key).unwrap());
+            let moved = plan.iter().find(|(k, _, _)| k == key);
+            match moved {
+                Some((_, from, to)) => assert_eq!((from, to), (&old, &new)),
+                None => assert_eq!(old, new),
+            }
+        }
+    }
+}